@@ -0,0 +1,209 @@
+//! Third-party front-end assets.
+//!
+//! Scripts and styles are served from pinned copies under `static/vendor/`, fetched with
+//! the `vendor` command and checked in, so an export works offline and nothing changes
+//! when upstream publishes a release. Setting `assets.cdn` in the site configuration
+//! links the same pinned versions on jsDelivr instead. Missing copies are reported by
+//! `check` and `build`, never replaced by the CDN behind the configuration's back.
+//!
+//! Every file is checked against an integrity hash pinned here, never one computed from
+//! the download being checked. `vendor` refuses files that have none yet.
+
+use crate::site;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use maud::{html, Markup};
+use sha2::{Digest, Sha384};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const CDN: &str = "https://cdn.jsdelivr.net/npm";
+
+#[derive(Debug)]
+pub struct Asset {
+    pub package: &'static str,
+    pub version: &'static str,
+    /// Path inside the package, kept the same under `static/vendor/`.
+    pub file: &'static str,
+    pinned: Option<&'static str>,
+}
+
+impl Asset {
+    const fn new(
+        package: &'static str,
+        version: &'static str,
+        file: &'static str,
+        pinned: Option<&'static str>,
+    ) -> Self {
+        Asset {
+            package,
+            version,
+            file,
+            pinned,
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        local_path(self.package, self.version, self.file)
+    }
+
+    fn cdn_url(&self) -> String {
+        cdn_url(self.package, self.version, self.file)
+    }
+
+    pub fn href(&self) -> String {
+        match site::get().assets.cdn {
+            true => self.cdn_url(),
+            false => format!(
+                "/static/vendor/{}@{}/{}",
+                self.package, self.version, self.file
+            ),
+        }
+    }
+
+    /// The subresource integrity of the file, if pinned.
+    pub fn integrity(&self) -> Option<&'static str> {
+        self.pinned
+    }
+
+    pub fn script(&self) -> Markup {
+        html! {
+            script defer src=(self.href()) integrity=[self.integrity()] crossorigin="anonymous" {}
+        }
+    }
+
+    pub fn stylesheet(&self) -> Markup {
+        html! {
+            link rel="stylesheet" href=(self.href()) integrity=[self.integrity()] crossorigin="anonymous" {}
+        }
+    }
+}
+
+/// Its hash is not pinned yet, so `vendor` stops here until it is.
+pub static ALPINE: Asset = Asset::new("alpinejs", "3.14.1", "dist/cdn.min.js", None);
+
+pub static KATEX_JS: Asset = Asset::new(
+    "katex",
+    "0.16.0",
+    "dist/katex.min.js",
+    Some("sha384-X/XCfMm41VSsqRNQgDerQczD69XqmjOOOwYQvr/uuC+j4OPoNhVgjdGFwhvN02Ja"),
+);
+
+pub static KATEX_CSS: Asset = Asset::new(
+    "katex",
+    "0.16.0",
+    "dist/katex.min.css",
+    Some("sha384-Xi8rHCmBmhbuyyhbI88391ZKP2dmfnOl4rT9ZfRI7mLTdk1wblIUnrIq35nqwEvC"),
+);
+
+/// Fonts the KaTeX stylesheet loads relative to itself, not pinned yet either. Browsers
+/// that matter all take the WOFF2 versions, so the others are not vendored.
+const KATEX_FONTS: &[&str] = &[
+    "AMS-Regular",
+    "Caligraphic-Bold",
+    "Caligraphic-Regular",
+    "Fraktur-Bold",
+    "Fraktur-Regular",
+    "Main-Bold",
+    "Main-BoldItalic",
+    "Main-Italic",
+    "Main-Regular",
+    "Math-BoldItalic",
+    "Math-Italic",
+    "SansSerif-Bold",
+    "SansSerif-Italic",
+    "SansSerif-Regular",
+    "Script-Regular",
+    "Size1-Regular",
+    "Size2-Regular",
+    "Size3-Regular",
+    "Size4-Regular",
+    "Typewriter-Regular",
+];
+
+pub fn vendor_dir() -> PathBuf {
+    crate::export::static_dir().join("vendor")
+}
+
+fn local_path(package: &str, version: &str, file: &str) -> PathBuf {
+    vendor_dir().join(format!("{package}@{version}")).join(file)
+}
+
+fn cdn_url(package: &str, version: &str, file: &str) -> String {
+    format!("{CDN}/{package}@{version}/{file}")
+}
+
+fn integrity(bytes: &[u8]) -> String {
+    let digest = Sha384::digest(bytes);
+    format!(
+        "sha384-{}",
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    )
+}
+
+/// Every file `vendor` fetches, as its URL, its local path and its pinned hash.
+fn files() -> Vec<(String, PathBuf, Option<&'static str>)> {
+    let mut files: Vec<_> = [&ALPINE, &KATEX_JS, &KATEX_CSS]
+        .into_iter()
+        .map(|asset| (asset.cdn_url(), asset.path(), asset.pinned))
+        .collect();
+    let (package, version) = (KATEX_CSS.package, KATEX_CSS.version);
+    files.extend(KATEX_FONTS.iter().map(|font| {
+        let file = format!("dist/fonts/KaTeX_{font}.woff2");
+        (
+            cdn_url(package, version, &file),
+            local_path(package, version, &file),
+            None,
+        )
+    }));
+    files
+}
+
+/// Vendored files that are not on disk.
+pub fn missing() -> Vec<PathBuf> {
+    files()
+        .into_iter()
+        .map(|(_, path, _)| path)
+        .filter(|path| !path.exists())
+        .collect()
+}
+
+fn download(url: &str, path: &Path) -> Result<()> {
+    let status = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--create-dirs",
+        ])
+        .arg("--output")
+        .arg(path)
+        .arg(url)
+        .status()
+        .context("failed to run curl, is it installed?")?;
+    if !status.success() {
+        bail!("failed to download {url}");
+    }
+    Ok(())
+}
+
+/// Downloads the pinned version of every asset into `static/vendor/`, checking each
+/// file against its pinned hash.
+pub fn vendor() -> Result<()> {
+    for (url, path, pinned) in files() {
+        let Some(pinned) = pinned else {
+            bail!("{url} has no pinned integrity hash, pin the one upstream publishes first");
+        };
+        println!("    Fetching {url}");
+        download(&url, &path)?;
+
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        if integrity(&bytes) != pinned {
+            let _ = std::fs::remove_file(&path);
+            bail!("{url} does not match its pinned integrity hash");
+        }
+    }
+    Ok(())
+}
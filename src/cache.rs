@@ -0,0 +1,66 @@
+//! In-memory cache of rendered posts.
+//!
+//! Rendering runs the whole markdown and highlighting pipeline, so `/blog/:name/` keeps
+//! the pages it rendered last. Entries are keyed by slug and checked against a hash of
+//! the post's source, so an edited post is rendered again even without `--watch`.
+
+use crate::error::RenderError;
+use crate::post::Post;
+use crate::site;
+use lru::LruCache;
+use maud::Markup;
+use std::num::NonZeroUsize;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// Response header telling whether the page came from the cache.
+pub const HEADER: &str = "x-cache";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Hit,
+    Miss,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Hit => "hit",
+            Status::Miss => "miss",
+        }
+    }
+}
+
+type Pages = LruCache<String, (u64, Markup)>;
+
+/// `None` when the cache is disabled with a size of zero.
+static PAGES: LazyLock<Option<Mutex<Pages>>> = LazyLock::new(|| {
+    NonZeroUsize::new(site::get().cache.size).map(|size| Mutex::new(LruCache::new(size)))
+});
+
+fn lock(pages: &Mutex<Pages>) -> MutexGuard<'_, Pages> {
+    // A panic while holding the lock cannot leave a half-written entry behind
+    pages.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The rendered page for `post`, reusing the last rendering while the source is unchanged.
+pub fn page(post: &Post) -> Result<(Markup, Status), RenderError> {
+    let Some(pages) = &*PAGES else {
+        return Ok((crate::markdown(post)?, Status::Miss));
+    };
+
+    if let Some((hash, page)) = lock(pages).get(&post.slug) {
+        if *hash == post.hash {
+            return Ok((page.clone(), Status::Hit));
+        }
+    }
+
+    let page = crate::markdown(post)?;
+    lock(pages).put(post.slug.clone(), (post.hash, page.clone()));
+    Ok((page, Status::Miss))
+}
+
+pub fn clear() {
+    if let Some(pages) = &*PAGES {
+        lock(pages).clear();
+    }
+}
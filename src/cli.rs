@@ -0,0 +1,142 @@
+//! Command-line interface.
+//!
+//! Exit codes: `0` on success, `1` when a command fails or `check` finds problems, and
+//! `2` for invalid usage.
+
+use crate::post;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Renders, serves and exports the site",
+    arg_required_else_help = true
+)]
+pub struct Cli {
+    /// Site configuration file
+    #[arg(long, global = true, default_value_os_t = crate::site::default_path())]
+    pub config: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the site over HTTP
+    Serve {
+        /// Address to listen on, overrides `host` in the site configuration
+        #[arg(long)]
+        host: Option<String>,
+        /// Port to listen on, overrides `port` in the site configuration
+        #[arg(long)]
+        port: Option<u16>,
+        /// Reload open pages when posts or static assets change
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Export the whole site as static files
+    Build {
+        /// Directory to write the site to, replacing an earlier export there
+        #[arg(long, default_value_os_t = crate::export::default_out_dir())]
+        out_dir: PathBuf,
+    },
+    /// Scaffold a new draft post
+    New {
+        /// Lowercase words joined by hyphens, e.g. `my-new-post`
+        slug: String,
+        /// Title of the post, derived from the slug when missing
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Validate the configuration and every post without writing anything
+    Check,
+    /// Download the pinned front-end assets into `static/vendor/`
+    Vendor,
+    /// Regenerate the stylesheets with the Tailwind CLI, if it is installed
+    BuildCss,
+}
+
+fn title_from_slug(slug: &str) -> String {
+    let title = slug.replace('-', " ");
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => title,
+    }
+}
+
+/// Writes `blog/<slug>.md` with draft front matter dated today.
+pub fn new_post(slug: &str, title: Option<String>) -> Result<PathBuf> {
+    if !post::is_valid_slug(slug) {
+        bail!("invalid slug `{slug}`, use lowercase letters and digits joined by single hyphens");
+    }
+
+    let path = post::blog_dir().join(format!("{slug}.md"));
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    let title = title.unwrap_or_else(|| title_from_slug(slug));
+    let contents = format!(
+        "---\ntitle: {}\ndate: {}\ndescription: \"\"\ntags: []\ndraft: true\n---\n\n# {title}\n\n",
+        serde_json::to_string(&title).expect("a string always serializes"),
+        chrono::Local::now().format("%Y-%m-%d"),
+    );
+    std::fs::write(&path, contents)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+
+    Ok(path)
+}
+
+/// Loads and renders every post, reporting all problems instead of stopping at the first.
+pub fn check() -> Result<ExitCode> {
+    let mut problems = 0;
+    let paths = post::paths()?;
+
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let result = post::load(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|post| {
+                if !post::is_valid_slug(&post.slug) {
+                    bail!("`{}` is not a valid slug", post.slug);
+                }
+                crate::markdown(&post)?;
+                Ok(())
+            });
+
+        match result {
+            Ok(()) => println!("    ok    {name}"),
+            Err(err) => {
+                problems += 1;
+                println!("    FAIL  {name}: {err:#}");
+            }
+        }
+    }
+
+    for path in crate::css::missing() {
+        problems += 1;
+        println!("    FAIL  {}: missing, run `build-css`", path.display());
+    }
+
+    if !crate::site::get().assets.cdn {
+        for path in crate::assets::missing() {
+            problems += 1;
+            println!(
+                "    FAIL  {}: missing, run `vendor` or set `assets.cdn`",
+                path.display()
+            );
+        }
+    }
+
+    println!("{} posts checked, {problems} problems", paths.len());
+    Ok(if problems == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
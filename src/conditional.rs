@@ -0,0 +1,105 @@
+//! Conditional GET for rendered pages.
+//!
+//! Every successful page gets a weak `ETag` hashed from its body, and handlers that
+//! know when their content changed add a `Last-Modified`. Requests whose validators
+//! still match are answered with an empty `304 Not Modified`. `If-None-Match` wins over
+//! `If-Modified-Since` when a client sends both, as RFC 9110 asks.
+//!
+//! The tag is weak because it is computed before compression: the gzip, brotli and
+//! identity responses share it, and RFC 9110 reserves strong tags for identical bytes.
+
+use axum::body::{to_bytes, Body};
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use http::header::{CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// A `Last-Modified` header for handlers to return alongside their page, empty when the
+/// time is unknown.
+pub fn last_modified(time: Option<DateTime<Utc>>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(time) = time {
+        let value = time.format(HTTP_DATE).to_string();
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_str(&value).expect("a formatted date is a valid header"),
+        );
+    }
+    headers
+}
+
+fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let mut tag = String::from("W/\"");
+    for byte in &digest[..16] {
+        write!(tag, "{byte:02x}").expect("writing to a string cannot fail");
+    }
+    tag.push('"');
+    tag
+}
+
+fn parse_date(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    let value = value.to_str().ok()?;
+    Some(
+        DateTime::parse_from_rfc2822(value)
+            .ok()?
+            .with_timezone(&Utc),
+    )
+}
+
+/// Whether the client's copy, described by the request headers, is still current.
+fn not_modified(request: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(tags) = request.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        // GET compares weakly, so a `W/` prefix does not matter
+        let etag = etag.trim_start_matches("W/");
+        return tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag);
+    }
+
+    match (
+        request.get(IF_MODIFIED_SINCE).and_then(parse_date),
+        modified,
+    ) {
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+pub async fn middleware(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let headers = request.headers().clone();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let etag = etag(&body);
+    let modified = parts.headers.get(LAST_MODIFIED).and_then(parse_date);
+    parts.headers.insert(
+        ETAG,
+        HeaderValue::from_str(&etag).expect("a hex digest is a valid header"),
+    );
+
+    if not_modified(&headers, &etag, modified) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
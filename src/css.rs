@@ -0,0 +1,55 @@
+//! Stylesheets generated by the Tailwind CLI.
+//!
+//! Generating them is an explicit `build-css` step rather than part of `cargo build`, so
+//! the site compiles on machines without the Tailwind CLI. When `tailwindcss` is not
+//! installed the step only warns, and the existing `static/styles.css` and
+//! `static/blog_styles.css` are served as they are. Until they have been generated once,
+//! `check` fails and `build` warns, since every page would come out unstyled.
+
+use anyhow::{bail, Context, Result};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Each stylesheet with the input it is generated from, if any besides the config.
+const STYLESHEETS: [(Option<&str>, &str); 2] = [
+    (None, "static/styles.css"),
+    (Some("src/input.css"), "static/blog_styles.css"),
+];
+
+/// Stylesheets that were never generated, which leaves every page unstyled.
+pub fn missing() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    STYLESHEETS
+        .into_iter()
+        .map(|(_, output)| root.join(output))
+        .filter(|path| !path.exists())
+        .collect()
+}
+
+pub fn build() -> Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for (input, output) in STYLESHEETS {
+        let mut tailwind = Command::new("tailwindcss");
+        tailwind
+            .current_dir(root)
+            .args(["-c", "./tailwind.config.js"]);
+        if let Some(input) = input {
+            tailwind.args(["-i", input]);
+        }
+        tailwind.args(["-o", output]);
+
+        match tailwind.status() {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                eprintln!(
+                    "Warning: tailwindcss is not installed, keeping the existing stylesheets"
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err).context("failed to run tailwindcss"),
+            Ok(status) if !status.success() => bail!("tailwindcss failed to build {output}"),
+            Ok(_) => println!("    Built {output}"),
+        }
+    }
+    Ok(())
+}
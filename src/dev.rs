@@ -0,0 +1,89 @@
+//! Live reload for `serve --watch`.
+//!
+//! A file system watcher over the content directories broadcasts a message on every
+//! change, and each open page listens for it on a server-sent events endpoint and
+//! reloads itself.
+
+use anyhow::{Context, Result};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+pub const ENDPOINT: &str = "/__livereload";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RELOAD: LazyLock<broadcast::Sender<()>> = LazyLock::new(|| broadcast::channel(16).0);
+
+/// Whether pages should carry the live reload client.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The script `BaseHtml` injects in dev mode.
+pub fn client_script() -> String {
+    format!("new EventSource('{ENDPOINT}').onmessage = () => location.reload();")
+}
+
+pub async fn events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(RELOAD.subscribe())
+        .map(|_| Ok(Event::default().event("message").data("reload")));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn watched() -> [PathBuf; 3] {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    [
+        root.join("blog"),
+        root.join("static"),
+        root.join("src/input.css"),
+    ]
+}
+
+/// Starts watching the content directories and turns on the live reload client.
+///
+/// The watcher stops when the returned handle is dropped.
+pub fn watch() -> Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(|res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(err) => return eprintln!("Watch error: {err}"),
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for path in &event.paths {
+            println!("    Changed {}", path.display());
+        }
+        // The stylesheet pages load is generated from it, so a reload alone shows nothing
+        if event
+            .paths
+            .iter()
+            .any(|path| path.ends_with("src/input.css"))
+        {
+            if let Err(err) = crate::css::build() {
+                eprintln!("Failed to build the stylesheets: {err:#}");
+            }
+        }
+        // Encoding can take minutes, and `refresh` clears the cache again once it is done
+        std::thread::spawn(crate::images::refresh);
+        crate::cache::clear();
+        crate::search::clear();
+        // Nobody listening just means no page is open
+        let _ = RELOAD.send(());
+    })?;
+
+    for path in watched() {
+        watcher
+            .watch(&path, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch {}", path.display()))?;
+    }
+
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(watcher)
+}
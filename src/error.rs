@@ -0,0 +1,90 @@
+//! Errors raised while turning a post into a page.
+
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum RenderError {
+    /// No post exists under the requested slug.
+    MissingPost(String),
+    /// A listing page, such as a tag or an archive period, that has no posts.
+    MissingPage(String),
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The post exists but is not valid UTF-8.
+    InvalidUtf8 {
+        path: PathBuf,
+        source: std::string::FromUtf8Error,
+    },
+    FrontMatter {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+    Highlight(syntect::Error),
+    /// An image in a post could not be read or converted.
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+}
+
+impl RenderError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RenderError::MissingPost(_) | RenderError::MissingPage(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::MissingPost(slug) => write!(f, "there is no post named `{slug}`"),
+            RenderError::MissingPage(path) => write!(f, "there is nothing at `{path}`"),
+            RenderError::Io { path, source } => {
+                write!(f, "failed to read {}: {source}", path.display())
+            }
+            RenderError::InvalidUtf8 { path, source } => {
+                write!(f, "{} is not valid UTF-8: {source}", path.display())
+            }
+            RenderError::FrontMatter { path, source } => {
+                write!(f, "bad front matter in {}: {source:#}", path.display())
+            }
+            RenderError::Highlight(source) => write!(f, "failed to highlight code: {source}"),
+            RenderError::Image { path, source } => {
+                write!(f, "failed to process image {}: {source}", path.display())
+            }
+        }
+    }
+}
+
+/// The causes are already part of the message, so they are not exposed again as sources.
+impl std::error::Error for RenderError {}
+
+impl From<syntect::Error> for RenderError {
+    fn from(err: syntect::Error) -> Self {
+        RenderError::Highlight(err)
+    }
+}
+
+impl IntoResponse for RenderError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let message = if let RenderError::MissingPost(_) = self {
+            "The post you are looking for does not exist."
+        } else if status == StatusCode::NOT_FOUND {
+            "The page you are looking for does not exist."
+        } else {
+            // The details are for the logs, not for visitors
+            eprintln!("Error: {self}");
+            "Something went wrong while rendering this page."
+        };
+
+        (status, crate::error_page(status, message)).into_response()
+    }
+}
@@ -0,0 +1,258 @@
+//! Static site export.
+//!
+//! Every known route is rendered straight to `<out_dir>/<route>/index.html`, so the
+//! output can be hosted as-is by any static file server. Nothing in here depends on
+//! the clock or on directory iteration order, which keeps the export byte-identical
+//! from one run to the next.
+//!
+//! Text files also get gzip and brotli compressed siblings, `page.html.gz` and
+//! `page.html.br`, for servers that send those as-is to clients accepting them.
+
+use crate::post::{self, Post};
+use crate::{feed, sitemap};
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use maud::Markup;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Written by every export before anything else, so the next one knows the directory
+/// is safe to replace.
+const MARKER: &str = ".export";
+
+/// Formats worth compressing. Images and fonts are compressed already.
+const COMPRESSIBLE: &[&str] = &["html", "css", "js", "json", "xml", "txt", "svg"];
+
+pub fn static_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/static"))
+}
+
+pub fn default_out_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/target/site"))
+}
+
+/// Writes `page` as the `index.html` of `route`, a path relative to the site root.
+fn write_page(out_dir: &Path, route: &str, page: Markup) -> Result<()> {
+    write_file(
+        out_dir,
+        &format!("{route}index.html"),
+        page.into_string().as_bytes(),
+    )
+}
+
+fn write_file(out_dir: &Path, route: &str, contents: &[u8]) -> Result<()> {
+    let path = out_dir.join(route.trim_start_matches('/'));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("failed to create {}", to.display()))?;
+
+    let entries =
+        fs::read_dir(from).with_context(|| format!("failed to read {}", from.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target).with_context(|| {
+                format!(
+                    "failed to copy {} to {}",
+                    entry.path().display(),
+                    target.display()
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the whole site into `out_dir`, replacing a previous export there. Any other
+/// non-empty directory is left alone, so a mistyped `--out-dir` cannot wipe a checkout.
+pub fn export(out_dir: &Path) -> Result<()> {
+    if out_dir.exists() {
+        let empty = fs::read_dir(out_dir)
+            .with_context(|| format!("failed to read {}", out_dir.display()))?
+            .next()
+            .is_none();
+        if !empty && !out_dir.join(MARKER).is_file() {
+            bail!(
+                "refusing to replace {}, it is not empty and holds no previous export",
+                out_dir.display()
+            );
+        }
+        println!("Removing old {}", out_dir.display());
+        fs::remove_dir_all(out_dir)
+            .with_context(|| format!("failed to remove {}", out_dir.display()))?;
+    }
+    // First thing, so an export that fails halfway can still be replaced by the next one
+    write_file(out_dir, MARKER, b"")?;
+
+    let posts: Vec<Post> = post::published()?;
+
+    // Before rendering anything, so posts offer the variants
+    println!("    Encoding images");
+    crate::images::generate(&posts)?;
+
+    println!("    Pages");
+    write_page(out_dir, "", crate::home())?;
+    write_page(out_dir, "contact/", crate::contact())?;
+    write_page(out_dir, "blog/", crate::blog(&posts))?;
+
+    // Picked up by static hosts such as GitHub Pages for unknown paths
+    write_file(
+        out_dir,
+        "404.html",
+        crate::error_page(
+            http::StatusCode::NOT_FOUND,
+            "The page you are looking for does not exist.",
+        )
+        .into_string()
+        .as_bytes(),
+    )?;
+
+    println!("    Tags");
+    let tags = post::tags(&posts);
+    write_page(out_dir, "blog/tags/", crate::tag_index(&tags))?;
+    for tag in &tags {
+        write_page(
+            out_dir,
+            &format!("blog/tags/{}/", tag.slug),
+            crate::tag_page(&tag.slug, &posts)?,
+        )?;
+    }
+
+    println!("    Archive");
+    write_page(out_dir, "blog/archive/", crate::archive_index(&posts))?;
+    for (year, months) in post::archive(&posts) {
+        write_page(
+            out_dir,
+            &format!("blog/{year}/"),
+            crate::archive_page(year, None, &posts)?,
+        )?;
+        for month in months.into_keys() {
+            write_page(
+                out_dir,
+                &format!("blog/{year}/{month:02}/"),
+                crate::archive_page(year, Some(month), &posts)?,
+            )?;
+        }
+    }
+
+    println!("    Posts");
+    for post in &posts {
+        write_page(
+            out_dir,
+            &format!("blog/{}/", post.slug),
+            crate::markdown(post)?,
+        )?;
+    }
+
+    println!("    Images");
+    let images = out_dir.join(crate::images::ROUTE.trim_matches('/'));
+    fs::create_dir_all(&images)
+        .with_context(|| format!("failed to create {}", images.display()))?;
+    for name in crate::images::used() {
+        fs::copy(crate::images::cache_dir().join(&name), images.join(&name))
+            .with_context(|| format!("failed to copy image {name}"))?;
+    }
+
+    println!("    Feeds and sitemap");
+    write_file(out_dir, feed::RSS_PATH, feed::rss(&posts)?.as_bytes())?;
+    write_file(out_dir, feed::ATOM_PATH, feed::atom(&posts)?.as_bytes())?;
+    write_file(out_dir, feed::JSON_PATH, feed::json(&posts)?.as_bytes())?;
+
+    write_file(
+        out_dir,
+        sitemap::SITEMAP_PATH,
+        sitemap::sitemap(&posts).as_bytes(),
+    )?;
+    write_file(out_dir, sitemap::ROBOTS_PATH, sitemap::robots().as_bytes())?;
+
+    println!("    Static assets");
+    for path in crate::css::missing() {
+        eprintln!(
+            "Warning: {} is missing, run `build-css` or pages export unstyled",
+            path.display()
+        );
+    }
+    if !crate::site::get().assets.cdn {
+        for path in crate::assets::missing() {
+            eprintln!(
+                "Warning: {} is missing, run `vendor` or set `assets.cdn`",
+                path.display()
+            );
+        }
+    }
+    copy_dir(&static_dir(), &out_dir.join("static"))?;
+    // Static hosts cannot redirect `/favicon.ico` the way the server does
+    fs::copy(
+        static_dir().join("favicon.ico"),
+        out_dir.join("favicon.ico"),
+    )
+    .context("failed to copy favicon.ico")?;
+
+    println!("    Compressing");
+    precompress(out_dir)?;
+
+    Ok(())
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// Writes the `.gz` and `.br` versions of every compressible file under `dir`.
+fn precompress(dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            precompress(&path)?;
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSIBLE.contains(&ext));
+        if !compressible {
+            continue;
+        }
+
+        let contents =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let context = || format!("failed to compress {}", path.display());
+
+        // The gzip header carries no timestamp, so the output only depends on the input
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+        gzip.write_all(&contents).with_context(context)?;
+        let gzip = gzip.finish().with_context(context)?;
+
+        let mut brotli = Vec::new();
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: 11,
+            ..Default::default()
+        };
+        brotli::BrotliCompress(&mut &contents[..], &mut brotli, &params).with_context(context)?;
+
+        for (extension, compressed) in [("gz", gzip), ("br", brotli)] {
+            let target = sibling(&path, extension);
+            fs::write(&target, compressed)
+                .with_context(|| format!("failed to write {}", target.display()))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! RSS 2.0, Atom and JSON Feed documents for the blog.
+//!
+//! All three are built from the same published posts as the blog index and carry the
+//! full rendered body of every post. Timestamps come from the post metadata only, so a
+//! feed does not change unless the posts do.
+//!
+//! The reading time shown under a post's title comes along in its body, and the JSON
+//! Feed also lists it as a `_reading` extension for readers that want the numbers.
+
+use crate::error::RenderError;
+use crate::post::Post;
+use crate::site;
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub const RSS_PATH: &str = "/blog/feed.xml";
+pub const ATOM_PATH: &str = "/blog/atom.xml";
+pub const JSON_PATH: &str = "/blog/feed.json";
+
+pub fn title() -> String {
+    format!("Blog - {}", site::get().author)
+}
+
+fn timestamp(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc()
+}
+
+/// The date of the latest change to any post, used as the feed's own update time.
+fn updated(posts: &[Post]) -> DateTime<Utc> {
+    timestamp(
+        posts
+            .iter()
+            .map(Post::last_modified)
+            .max()
+            .unwrap_or_default(),
+    )
+}
+
+fn post_url(post: &Post) -> String {
+    let base = &site::get().url;
+    format!("{base}/blog/{}/", post.slug)
+}
+
+/// The rendered post with root-relative links made absolute, as feed readers resolve
+/// them against the feed and not the page.
+fn content(post: &Post) -> Result<String, RenderError> {
+    let base = &site::get().url;
+    let html = crate::post_html(post)?
+        .replace(r#"src="/"#, &format!(r#"src="{base}/"#))
+        .replace(r#"href="/"#, &format!(r#"href="{base}/"#));
+    Ok(absolute_srcsets(&html, base))
+}
+
+/// `html` with every URL in its `srcset`s made absolute. Each lists several URLs, each
+/// followed by the width it has.
+fn absolute_srcsets(html: &str, base: &str) -> String {
+    const ATTRIBUTE: &str = r#"srcset=""#;
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find(ATTRIBUTE) {
+        let value = &rest[at + ATTRIBUTE.len()..];
+        let Some(len) = value.find('"') else {
+            break;
+        };
+        out.push_str(&rest[..at + ATTRIBUTE.len()]);
+        let candidates: Vec<_> = value[..len]
+            .split(", ")
+            .map(|candidate| match candidate.starts_with('/') {
+                true => format!("{base}{candidate}"),
+                false => candidate.to_string(),
+            })
+            .collect();
+        out.push_str(&candidates.join(", "));
+        rest = &value[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// RSS 2.0 requires RFC 822 dates, so unlike the other two feeds it uses RFC 2822.
+pub fn rss(posts: &[Post]) -> Result<String, RenderError> {
+    let site = site::get();
+    let base = &site.url;
+    let mut items = String::new();
+    for post in posts {
+        let url = post_url(post);
+        items.push_str(&format!(
+            "<item><title>{}</title><link>{url}</link><guid isPermaLink=\"true\">{url}</guid><pubDate>{}</pubDate><description>{}</description>{}</item>",
+            escape_xml(&post.meta.title),
+            timestamp(post.meta.date).to_rfc2822(),
+            escape_xml(&content(post)?),
+            post.meta
+                .tags
+                .iter()
+                .map(|tag| format!("<category>{}</category>", escape_xml(tag)))
+                .collect::<String>(),
+        ));
+    }
+
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>{}</title><link>{base}/blog/</link><description>{}</description><atom:link href="{base}{RSS_PATH}" rel="self" type="application/rss+xml"/><lastBuildDate>{}</lastBuildDate>{items}</channel></rss>"#,
+        escape_xml(&title()),
+        escape_xml(&site.description),
+        updated(posts).to_rfc2822(),
+    ))
+}
+
+pub fn atom(posts: &[Post]) -> Result<String, RenderError> {
+    let site = site::get();
+    let base = &site.url;
+    let mut entries = String::new();
+    for post in posts {
+        let url = post_url(post);
+        let published = timestamp(post.meta.date).to_rfc3339();
+        let updated = timestamp(post.last_modified()).to_rfc3339();
+        entries.push_str(&format!(
+            r#"<entry><title>{}</title><link href="{url}"/><id>{url}</id><published>{published}</published><updated>{updated}</updated><summary>{}</summary><content type="html">{}</content>{}</entry>"#,
+            escape_xml(&post.meta.title),
+            escape_xml(&post.meta.description),
+            escape_xml(&content(post)?),
+            post.meta
+                .tags
+                .iter()
+                .map(|tag| format!(r#"<category term="{}"/>"#, escape_xml(tag)))
+                .collect::<String>(),
+        ));
+    }
+
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>{}</title><subtitle>{}</subtitle><link href="{base}/blog/"/><link href="{base}{ATOM_PATH}" rel="self"/><id>{base}/blog/</id><updated>{}</updated><author><name>{}</name></author>{entries}</feed>"#,
+        escape_xml(&title()),
+        escape_xml(&site.description),
+        updated(posts).to_rfc3339(),
+        escape_xml(&site.author),
+    ))
+}
+
+pub fn json(posts: &[Post]) -> Result<String, RenderError> {
+    let site = site::get();
+    let base = &site.url;
+    let items = posts
+        .iter()
+        .map(|post| {
+            Ok(serde_json::json!({
+                "id": post_url(post),
+                "url": post_url(post),
+                "title": post.meta.title,
+                "summary": post.meta.description,
+                "content_html": content(post)?,
+                "date_published": timestamp(post.meta.date).to_rfc3339(),
+                "date_modified": timestamp(post.last_modified()).to_rfc3339(),
+                "tags": post.meta.tags,
+                "_reading": {
+                    "words": post.reading.words,
+                    "minutes": post.reading.minutes(),
+                },
+            }))
+        })
+        .collect::<Result<Vec<_>, RenderError>>()?;
+
+    let feed = serde_json::json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": title(),
+        "description": site.description,
+        "home_page_url": format!("{base}/blog/"),
+        "feed_url": format!("{base}{JSON_PATH}"),
+        "authors": [{ "name": site.author }],
+        "items": items,
+    });
+
+    Ok(serde_json::to_string_pretty(&feed).expect("a JSON value always serializes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srcsets_are_absolute() {
+        let html = r#"<source srcset="/images/a-480.avif 480w, /images/a-960.avif 960w"><img src="/static/a.png" srcset="https://cdn.example/b.png 2x">"#;
+        assert_eq!(
+            absolute_srcsets(html, "https://example.com"),
+            r#"<source srcset="https://example.com/images/a-480.avif 480w, https://example.com/images/a-960.avif 960w"><img src="/static/a.png" srcset="https://cdn.example/b.png 2x">"#
+        );
+    }
+}
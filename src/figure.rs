@@ -0,0 +1,138 @@
+//! Numbered figures and references to them.
+//!
+//! An image alone in its paragraph is a figure: `render_post` wraps it in a `<figure>`
+//! captioned with its alt text and numbered in order of appearance. Text refers to a
+//! figure as `@fig:<name>`, where the name is the image's file name without its
+//! extension, and the reference becomes a "Figure N" link whether it comes before or
+//! after the figure.
+
+use crate::toc::Ids;
+use maud::{html, Markup};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::Path;
+
+const REFERENCE: &str = "@fig:";
+
+#[derive(Debug, Clone)]
+pub struct Figure {
+    pub number: usize,
+    pub id: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Figures {
+    /// Figures by the index of the paragraph event that holds them.
+    paragraphs: HashMap<usize, Figure>,
+    /// Figures by name, for references. A name used twice refers to its first figure.
+    names: HashMap<String, Figure>,
+}
+
+/// The name references use for the image at `src`.
+fn name(src: &str) -> String {
+    let stem = Path::new(src)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    crate::post::tag_slug(stem)
+}
+
+impl Figures {
+    /// Numbers the image-only paragraphs among `events`, taking their IDs from `ids`.
+    pub fn find(events: &[Event], ids: &mut Ids) -> Self {
+        let mut figures = Figures::default();
+        for (i, window) in events.windows(2).enumerate() {
+            let [Event::Start(Tag::Paragraph), Event::Start(Tag::Image { dest_url, .. })] = window
+            else {
+                continue;
+            };
+            let Some(end) = events[i + 1..]
+                .iter()
+                .position(|event| matches!(event, Event::End(TagEnd::Image)))
+            else {
+                continue;
+            };
+            if !matches!(
+                events.get(i + 1 + end + 1),
+                Some(Event::End(TagEnd::Paragraph))
+            ) {
+                continue;
+            }
+
+            let name = name(dest_url);
+            let figure = Figure {
+                number: figures.paragraphs.len() + 1,
+                id: ids.unique(&format!("fig {name}")),
+            };
+            if !name.is_empty() {
+                figures.names.entry(name).or_insert_with(|| figure.clone());
+            }
+            figures.paragraphs.insert(i, figure);
+        }
+        figures
+    }
+
+    /// The figure whose paragraph starts at event `index`, if that paragraph is one.
+    pub fn at(&self, index: usize) -> Option<&Figure> {
+        self.paragraphs.get(&index)
+    }
+
+    /// `text` with every `@fig:<name>` of a known figure turned into a link, or `None`
+    /// when there is nothing to link. References to unknown names are left as written.
+    pub fn link(&self, text: &str) -> Option<Markup> {
+        if !text.contains(REFERENCE) {
+            return None;
+        }
+
+        // Each part is some text followed by the figure it references, if any
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(at) = rest.find(REFERENCE) {
+            let name = &rest[at + REFERENCE.len()..];
+            let len = name
+                .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(name.len());
+            let end = at + REFERENCE.len() + len;
+            match self.names.get(&crate::post::tag_slug(&name[..len])) {
+                Some(figure) => parts.push((&rest[..at], Some(figure))),
+                None => parts.push((&rest[..end], None)),
+            }
+            rest = &rest[end..];
+        }
+        parts.push((rest, None));
+
+        Some(html! {
+            @for (text, figure) in parts {
+                (text)
+                @if let Some(figure) = figure {
+                    a href=(format!("#{}", figure.id)) { "Figure " (figure.number) }
+                }
+            }
+        })
+    }
+}
+
+/// The alt text without a hand-typed "Figure 3:" in front, now that numbering is automatic.
+pub fn caption(alt: &str) -> &str {
+    let Some(number) = alt.strip_prefix("Figure") else {
+        return alt;
+    };
+    let number = number.trim_start();
+    let rest = number.trim_start_matches(|c: char| c.is_ascii_digit());
+    match rest.strip_prefix([':', '.']) {
+        Some(caption) if rest.len() < number.len() => caption.trim_start(),
+        _ => alt,
+    }
+}
+
+pub fn render(figure: &Figure, image: Markup, caption: &str) -> Markup {
+    html! {
+        figure id=(figure.id) class="my-6" {
+            div style="border-radius: 3pt;" class="bg-white p-3" { (image) }
+            figcaption class="text-sm font-light text-center pt-1" {
+                "Figure " (figure.number)
+                @if !caption.is_empty() { ": " (caption) }
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Footnotes, gathered into a notes section at the end of the post.
+//!
+//! pulldown-cmark renders a footnote definition wherever it appears in the source and
+//! numbers nothing, so `render_post` hands both halves over here instead. Notes are
+//! numbered in the order they are first referenced, and every reference gets a link
+//! back from its note. Definitions nobody references are left out, like on GitHub.
+
+use crate::toc::Ids;
+use maud::{html, Markup, PreEscaped};
+use pulldown_cmark::{CowStr, Event, TagEnd};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Footnotes<'a> {
+    /// Labels in order of first reference, so a label's number is its index plus one.
+    order: Vec<CowStr<'a>>,
+    /// How many times each label has been referenced so far.
+    references: HashMap<CowStr<'a>, usize>,
+    definitions: HashMap<CowStr<'a>, Vec<Event<'a>>>,
+}
+
+fn reference_id(number: usize, nth: usize) -> String {
+    match nth {
+        1 => format!("fnref-{number}"),
+        nth => format!("fnref-{number}-{nth}"),
+    }
+}
+
+/// Takes the IDs the notes and their references will get from `Footnotes`, so no
+/// heading among `events` ends up with one of them.
+pub fn reserve(events: &[Event], ids: &mut Ids) {
+    let mut numbers = HashMap::<&str, usize>::new();
+    let mut references = HashMap::<&str, usize>::new();
+    for event in events {
+        let Event::FootnoteReference(label) = event else {
+            continue;
+        };
+        let next = numbers.len() + 1;
+        let number = *numbers.entry(label.as_ref()).or_insert(next);
+        let nth = references.entry(label.as_ref()).or_default();
+        *nth += 1;
+        if *nth == 1 {
+            ids.reserve(&format!("fn-{number}"));
+        }
+        ids.reserve(&reference_id(number, *nth));
+    }
+}
+
+impl<'a> Footnotes<'a> {
+    /// The superscript link shown where a footnote is referenced.
+    pub fn reference(&mut self, label: CowStr<'a>) -> Markup {
+        let nth = self.references.entry(label.clone()).or_default();
+        *nth += 1;
+        let nth = *nth;
+        if nth == 1 {
+            self.order.push(label.clone());
+        }
+        let number = 1 + self
+            .order
+            .iter()
+            .position(|known| *known == label)
+            .expect("referenced labels are always in the order");
+
+        html! {
+            sup class="footnote-reference" id=(reference_id(number, nth)) {
+                a href=(format!("#fn-{number}")) { (number) }
+            }
+        }
+    }
+
+    /// Records the already processed body of a footnote definition.
+    pub fn define(&mut self, label: CowStr<'a>, body: Vec<Event<'a>>) {
+        self.definitions.insert(label, body);
+    }
+
+    /// The notes section, or `None` when the post references no footnotes.
+    pub fn render(mut self) -> Option<Markup> {
+        let notes: Vec<_> = self
+            .order
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| {
+                let mut body = self.definitions.remove(label)?;
+                let number = i + 1;
+                let backlinks = html! {
+                    @for nth in 1..=self.references[label] {
+                        " "
+                        a class="footnote-backref" href=(format!("#{}", reference_id(number, nth))) aria-label="Back to the text" {
+                            "↩"
+                            @if nth > 1 { sup { (nth) } }
+                        }
+                    }
+                };
+
+                // Keep the links on the last line of the note instead of one of their own
+                let at = match body.last() {
+                    Some(Event::End(TagEnd::Paragraph)) => body.len() - 1,
+                    _ => body.len(),
+                };
+                body.insert(at, Event::InlineHtml(backlinks.into_string().into()));
+
+                let mut html = String::new();
+                pulldown_cmark::html::push_html(&mut html, body.into_iter());
+                Some((number, html))
+            })
+            .collect();
+
+        if notes.is_empty() {
+            return None;
+        }
+
+        Some(html! {
+            section class="footnotes" {
+                ol {
+                    @for (number, html) in notes {
+                        li id=(format!("fn-{number}")) { (PreEscaped(html)) }
+                    }
+                }
+            }
+        })
+    }
+}
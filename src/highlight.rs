@@ -0,0 +1,201 @@
+//! In-process syntax highlighting.
+//!
+//! Code is tokenized with syntect and every token is wrapped in a span carrying the
+//! short chroma class names (`k`, `nf`, `s2`, ...) that `src/input.css` already styles,
+//! each one paired with its `dark:` variant, e.g. `class="dark:k k"`.
+
+use std::sync::LazyLock;
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// TextMate scope prefixes and the chroma class they render as, most specific first.
+///
+/// A `None` class defers to the enclosing scope, so the quotes of a string or the `//`
+/// of a comment take the string or comment colour instead of the punctuation one.
+const CLASSES: &[(&str, Option<&str>)] = &[
+    ("punctuation.definition.comment", None),
+    ("punctuation.definition.string", None),
+    ("punctuation.definition.character", None),
+    ("comment.line.documentation", Some("sd")),
+    ("comment.block.documentation", Some("sd")),
+    ("comment.line", Some("c1")),
+    ("comment.block", Some("cm")),
+    ("comment", Some("c")),
+    ("constant.character.escape", Some("se")),
+    ("constant.numeric.integer", Some("mi")),
+    ("constant.numeric.float", Some("mf")),
+    ("constant.numeric.hex", Some("mh")),
+    ("constant.numeric", Some("m")),
+    ("constant.language", Some("kc")),
+    ("constant.character", Some("sc")),
+    ("constant", Some("no")),
+    ("string.quoted.double", Some("s2")),
+    ("string.quoted.single", Some("s1")),
+    ("string.regexp", Some("sr")),
+    ("string", Some("s")),
+    ("keyword.operator.word", Some("ow")),
+    ("keyword.operator", Some("o")),
+    ("keyword.control.import", Some("kn")),
+    ("keyword.declaration", Some("kd")),
+    ("keyword", Some("k")),
+    ("storage.type", Some("kd")),
+    ("storage", Some("k")),
+    ("entity.name.function", Some("nf")),
+    ("entity.name.namespace", Some("nn")),
+    ("entity.name.module", Some("nn")),
+    ("entity.name.tag", Some("nt")),
+    ("entity.name.label", Some("nl")),
+    ("entity.name.constant", Some("no")),
+    ("entity.name.macro", Some("fm")),
+    ("entity.name", Some("nc")),
+    ("entity.other.attribute-name", Some("na")),
+    ("entity.other.inherited-class", Some("nc")),
+    ("support.function", Some("nb")),
+    ("support.macro", Some("fm")),
+    ("support.type", Some("kt")),
+    ("support.class", Some("nc")),
+    ("support.constant", Some("no")),
+    ("variable.language", Some("bp")),
+    ("variable.parameter", Some("nv")),
+    ("variable.function", Some("nf")),
+    ("variable.other.member", Some("py")),
+    ("variable.other.constant", Some("no")),
+    ("variable", Some("n")),
+    ("meta.annotation", Some("nd")),
+    ("markup.heading", Some("gh")),
+    ("markup.inserted", Some("gi")),
+    ("markup.deleted", Some("gd")),
+    ("markup.italic", Some("ge")),
+    ("markup.bold", Some("gs")),
+    ("markup.underline", Some("gl")),
+    ("invalid", Some("err")),
+    ("punctuation", Some("p")),
+];
+
+fn syntax(lang: &str) -> &'static SyntaxReference {
+    SYNTAX_SET
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+}
+
+fn class_of(scope: Scope) -> Option<Option<&'static str>> {
+    let name = scope.build_string();
+    CLASSES.iter().find_map(|(prefix, class)| {
+        let matches = name == *prefix
+            || (name.starts_with(prefix) && name.as_bytes().get(prefix.len()) == Some(&b'.'));
+        matches.then_some(*class)
+    })
+}
+
+/// The class of the innermost scope on the stack that has one.
+fn class_of_stack(stack: &ScopeStack) -> Option<&'static str> {
+    stack
+        .as_slice()
+        .iter()
+        .rev()
+        .find_map(|&scope| class_of(scope).flatten())
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn push_token(out: &mut String, class: Option<&str>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match class {
+        Some(class) => {
+            out.push_str(&format!(r#"<span class="dark:{class} {class}">"#));
+            push_escaped(out, text);
+            out.push_str("</span>");
+        }
+        None => push_escaped(out, text),
+    }
+}
+
+/// Highlights a single line (including its trailing newline, if any) into `out`.
+fn push_line(
+    out: &mut String,
+    state: &mut ParseState,
+    stack: &mut ScopeStack,
+    line: &str,
+) -> Result<(), syntect::Error> {
+    let ops = state.parse_line(line, &SYNTAX_SET)?;
+
+    // Adjacent tokens of the same class are merged into one span
+    let mut pending = String::new();
+    let mut pending_class = class_of_stack(stack);
+    let mut start = 0;
+
+    for (at, op) in ops {
+        if at > start {
+            let class = class_of_stack(stack);
+            if class != pending_class {
+                push_token(out, pending_class, &pending);
+                pending.clear();
+                pending_class = class;
+            }
+            pending.push_str(&line[start..at]);
+            start = at;
+        }
+        stack.apply(&op)?;
+    }
+
+    let class = class_of_stack(stack);
+    if class != pending_class {
+        push_token(out, pending_class, &pending);
+        pending.clear();
+        pending_class = class;
+    }
+    pending.push_str(&line[start..]);
+    push_token(out, pending_class, &pending);
+
+    Ok(())
+}
+
+/// Highlights a fenced code block as a `<pre>` with one `line` span per source line.
+pub fn block(code: &str, lang: &str) -> Result<String, syntect::Error> {
+    let mut state = ParseState::new(syntax(lang));
+    let mut stack = ScopeStack::new();
+    let mut out = String::from(r#"<pre class="dark:chroma chroma"><code>"#);
+
+    for line in LinesWithEndings::from(code) {
+        out.push_str(r#"<span class="dark:line line"><span class="dark:cl cl">"#);
+        push_line(&mut out, &mut state, &mut stack, line)?;
+        out.push_str("</span></span>");
+    }
+
+    out.push_str("</code></pre>");
+    Ok(out)
+}
+
+/// Highlights an inline code span as a bare `<code>` element.
+pub fn inline(code: &str, lang: &str) -> Result<String, syntect::Error> {
+    let mut state = ParseState::new(syntax(lang));
+    let mut stack = ScopeStack::new();
+    let mut out = String::from(r#"<code class="dark:chroma chroma">"#);
+
+    // The bundled syntaxes expect newline-terminated lines
+    let mut line = String::with_capacity(code.len() + 1);
+    line.push_str(code);
+    line.push('\n');
+    push_line(&mut out, &mut state, &mut stack, &line)?;
+
+    let newline = out
+        .rfind('\n')
+        .expect("the line just pushed ends in a newline");
+    out.remove(newline);
+    out.push_str("</code>");
+    Ok(out)
+}
@@ -0,0 +1,273 @@
+//! Responsive images for posts.
+//!
+//! `render_post` turns every image under `/static/` into a `<picture>` offering AVIF and
+//! WebP versions at a few widths, with the original as the fallback, explicit
+//! dimensions so the page does not jump while loading, and lazy loading. Variants are
+//! cached under `target/images/` by a hash of the source, so rebuilds only encode images
+//! that changed.
+//!
+//! Encoding takes seconds per image, so it never happens while rendering: `serve` encodes
+//! the missing variants in the background at startup and after every change under
+//! `--watch`, and `build` before exporting. Until then an image renders as a plain
+//! `<img>`.
+
+use crate::error::RenderError;
+use crate::post::Post;
+use image::codecs::avif::AvifEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader};
+use maud::{html, Markup};
+use pulldown_cmark::{Event, Parser, Tag};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where variants are served from and exported to.
+pub const ROUTE: &str = "/images/";
+
+/// Widths offered, skipping those wider than the original. The widest still covers the
+/// article column on a high-density screen, so larger originals are not offered as is.
+const WIDTHS: [u32; 3] = [480, 960, 1440];
+
+/// The article column is `max-w-screen-md` minus its padding.
+const SIZES: &str = "(min-width: 768px) 688px, 100vw";
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Avif,
+    Webp,
+}
+
+impl Format {
+    const ALL: [Format; 2] = [Format::Avif, Format::Webp];
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Avif => "avif",
+            Format::Webp => "webp",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            Format::Avif => "image/avif",
+            Format::Webp => "image/webp",
+        }
+    }
+
+    fn encode(self, image: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+        // Both encoders only take 8-bit RGB(A)
+        let image = DynamicImage::ImageRgba8(image.to_rgba8());
+        let mut bytes = Cursor::new(Vec::new());
+        match self {
+            Format::Avif => {
+                image.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut bytes, 8, 80))?
+            }
+            Format::Webp => image.write_to(&mut bytes, ImageFormat::WebP)?,
+        }
+        Ok(bytes.into_inner())
+    }
+}
+
+/// Variants referenced since startup, so an export only copies those.
+static USED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Held while encoding, so a rebuild started by `--watch` and the one at startup do not
+/// encode the same image twice.
+static ENCODING: Mutex<()> = Mutex::new(());
+
+pub fn cache_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/target/images"))
+}
+
+/// The file names of every variant referenced so far.
+pub fn used() -> Vec<String> {
+    let used = USED.lock().unwrap_or_else(|err| err.into_inner());
+    used.iter().cloned().collect()
+}
+
+/// The file behind a `/static/` URL, if it is a raster image the pipeline handles.
+fn source_path(src: &str) -> Option<PathBuf> {
+    let relative = Path::new(src.strip_prefix("/static/")?);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let extension = relative.extension()?.to_str()?.to_ascii_lowercase();
+    if !matches!(extension.as_str(), "png" | "jpg" | "jpeg") {
+        return None;
+    }
+    let path = crate::export::static_dir().join(relative);
+    path.is_file().then_some(path)
+}
+
+fn hash(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let mut hash = String::new();
+    for byte in &digest[..8] {
+        write!(hash, "{byte:02x}").expect("writing to a string cannot fail");
+    }
+    hash
+}
+
+/// The variants an image offers, named after a hash of its contents.
+struct Variants {
+    width: u32,
+    height: u32,
+    hash: String,
+    widths: Vec<u32>,
+}
+
+impl Variants {
+    /// Reads only as much of the image at `path` as it takes to know its dimensions.
+    fn of(path: &Path) -> Result<Self, ImageError> {
+        let bytes = std::fs::read(path)?;
+        let (width, height) = ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()?
+            .into_dimensions()?;
+
+        let mut widths: Vec<u32> = WIDTHS.into_iter().filter(|w| *w < width).collect();
+        if width <= WIDTHS[WIDTHS.len() - 1] {
+            widths.push(width);
+        }
+
+        Ok(Variants {
+            width,
+            height,
+            hash: hash(&bytes),
+            widths,
+        })
+    }
+
+    fn names(&self) -> impl Iterator<Item = (u32, Format, String)> + '_ {
+        self.widths.iter().flat_map(|width| {
+            Format::ALL.map(|format| {
+                (
+                    *width,
+                    format,
+                    format!("{}-{width}.{}", self.hash, format.extension()),
+                )
+            })
+        })
+    }
+
+    fn cached(&self) -> bool {
+        let dir = cache_dir();
+        self.names().all(|(_, _, name)| dir.join(name).exists())
+    }
+
+    /// Encodes the variants of the image at `path` missing from the cache.
+    fn generate(&self, path: &Path) -> Result<(), ImageError> {
+        let _encoding = ENCODING.lock().unwrap_or_else(|err| err.into_inner());
+        let dir = cache_dir();
+        std::fs::create_dir_all(&dir)?;
+
+        let mut image = None;
+        for (target, format, name) in self.names() {
+            let file = dir.join(&name);
+            if file.exists() {
+                continue;
+            }
+            let image = match &mut image {
+                Some(image) => image,
+                None => image.insert(image::open(path)?),
+            };
+            let resized = match target == self.width {
+                true => image.clone(),
+                false => image.resize(target, u32::MAX, FilterType::Lanczos3),
+            };
+
+            // Written aside first, so an interrupted build never leaves half a file
+            let partial = dir.join(format!("{name}.partial"));
+            std::fs::write(&partial, format.encode(&resized)?)?;
+            std::fs::rename(&partial, &file)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `src` of every image in a post's markdown.
+fn sources(markdown: &str) -> Vec<String> {
+    Parser::new_ext(markdown, crate::reading::options())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url.into_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Encodes the variants missing for the images in `posts`, returning how many images
+/// needed any. Slow for new images, so callers run it off the async runtime.
+pub fn generate(posts: &[Post]) -> Result<usize, RenderError> {
+    let mut generated = 0;
+    for src in posts.iter().flat_map(|post| sources(&post.body)) {
+        let Some(path) = source_path(&src) else {
+            continue;
+        };
+        let result = Variants::of(&path).and_then(|variants| {
+            if variants.cached() {
+                return Ok(());
+            }
+            println!("    Encoding {src}");
+            generated += 1;
+            variants.generate(&path)
+        });
+        result.map_err(|source| RenderError::Image { path, source })?;
+    }
+    Ok(generated)
+}
+
+/// The markup for an image in a post. Images outside `/static/` are left as plain `<img>`s.
+pub fn render(src: &str, alt: &str, title: &str) -> Result<Markup, RenderError> {
+    let title = (!title.is_empty()).then_some(title);
+    let Some(path) = source_path(src) else {
+        return Ok(html! {
+            img src=(src) alt=(alt) title=[title] loading="lazy" decoding="async";
+        });
+    };
+
+    let variants = Variants::of(&path).map_err(|source| RenderError::Image { path, source })?;
+    let img = html! {
+        img src=(src) alt=(alt) title=[title] width=(variants.width) height=(variants.height) loading="lazy" decoding="async";
+    };
+    // Until `generate` has encoded the variants, the original does on its own
+    if !variants.cached() {
+        return Ok(img);
+    }
+
+    USED.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .extend(variants.names().map(|(_, _, name)| name));
+    let srcset = |format: Format| {
+        variants
+            .widths
+            .iter()
+            .map(|w| format!("{ROUTE}{}-{w}.{} {w}w", variants.hash, format.extension()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    Ok(html! {
+        picture {
+            @for format in Format::ALL {
+                source type=(format.mime()) srcset=(srcset(format)) sizes=(SIZES);
+            }
+            (img)
+        }
+    })
+}
+
+/// Encodes what every post is missing for `serve`, then drops the pages rendered
+/// without it. Failures are only reported, since the originals still work.
+pub fn refresh() {
+    match crate::post::all().and_then(|posts| generate(&posts)) {
+        Ok(0) => {}
+        Ok(_) => crate::cache::clear(),
+        Err(err) => eprintln!("Failed to encode images: {err}"),
+    }
+}
@@ -1,23 +1,54 @@
 #![allow(dead_code)]
 
-use anyhow::Result;
+mod assets;
+mod cache;
+mod cli;
+mod conditional;
+mod css;
+mod dev;
+mod error;
+mod export;
+mod feed;
+mod figure;
+mod footnote;
+mod highlight;
+mod images;
+mod math;
+mod post;
+mod reading;
+mod search;
+mod site;
+mod sitemap;
+mod toc;
+
+use anyhow::{Context, Result};
 use axum::debug_handler;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
+use axum::handler::HandlerWithoutStateExt;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get_service;
 use axum::{response::Redirect, routing::get, Router};
+use chrono::Datelike;
+use clap::Parser as _;
+use cli::{Cli, Command};
+use error::RenderError;
+use http::{header, StatusCode};
 use maud::{html, Markup, PreEscaped, DOCTYPE};
-use pulldown_cmark::{CodeBlockKind, TagEnd};
-use std::io::Write;
-use std::process::Command;
-use std::process::Stdio;
-use std::str::FromStr;
-use std::{fs::OpenOptions, path::PathBuf};
-use tokio::fs::remove_dir_all;
+use post::Post;
+use pulldown_cmark::{CodeBlockKind, HeadingLevel, TagEnd};
+use site::Icon;
+use std::process::ExitCode;
 use tokio::net::TcpListener;
 
 #[debug_handler]
-async fn handle_error(/* err: std::io::Error */) -> (http::StatusCode, String) {
-    (http::StatusCode::NOT_FOUND, "File not found: ".to_string())
+async fn handle_error() -> (StatusCode, Markup) {
+    (
+        StatusCode::NOT_FOUND,
+        error_page(
+            StatusCode::NOT_FOUND,
+            "The page you are looking for does not exist.",
+        ),
+    )
 }
 
 fn footer() -> Markup {
@@ -27,7 +58,7 @@ fn footer() -> Markup {
                 div class="text-xs block text-center" {
                     // "Copyright © 2024 Alejandro Osornio (AOx0). All rights reserved."
                     // br {}
-                    "Made by AOx0 with "
+                    (site::get().footer) " "
                     a target="_blank" rel="noopener noreferrer" href="https://github.com/gbj/leptos" class="underline" { "Maud" }
                     ", "
                     a target="_blank" rel="noopener noreferrer" href="https://github.com/alpinejs/alpine" class="underline" { "AlpineJS" }
@@ -120,18 +151,21 @@ fn github() -> Markup {
 
 pub fn items_collection() -> Markup {
     html! {
-        (MenuItem { href: "/", children: (html!("Home")), ..Default::default() }.render())
-        (MenuItem { href: "/blog/", children: (html!("Blog")), ..Default::default() }.render())
-        (MenuItem { href: "/contact/", children: (html!("Contact")), ..Default::default() }.render())
-        // (MenuItem href="/portfolio" { "Portfolio" })
-        // (MenuItem href="/resume" { "Resume" })
+        @for item in &site::get().nav {
+            (MenuItem { href: &item.href, children: (html!((item.label))), ..Default::default() }.render())
+        }
     }
 }
 
 pub fn icons_collection() -> Markup {
     html! {
-        (MenuItem { href: r"https://www.linkedin.com/in/aox0/", nopage: true, children: (linked_in()), ..Default::default() }.render())
-        (MenuItem { href: r"https://github.com/aox0/", nopage: true, children: (github()), ..Default::default() }.render())
+        @for social in &site::get().social {
+            @let icon = match social.icon {
+                Icon::Github => github(),
+                Icon::Linkedin => linked_in(),
+            };
+            (MenuItem { href: &social.href, nopage: true, children: icon, ..Default::default() }.render())
+        }
     }
 }
 
@@ -140,7 +174,7 @@ pub fn aox0() -> Markup {
         href: r"/",
         children: (html! {
             h1 class="text-4xl font-bold" {
-                "AOx0"
+                (site::get().author)
             }
         }),
         ..Default::default()
@@ -210,12 +244,15 @@ impl<'src> BaseHtml<'src> {
                     meta name="viewport" content="width=device-width, initial-scale=1.0" {}
                     meta charset="UTF-8" {}
                     title {(self.title)}
+                    link rel="alternate" type="application/rss+xml" title=(feed::title()) href=(feed::RSS_PATH) {}
+                    link rel="alternate" type="application/atom+xml" title=(feed::title()) href=(feed::ATOM_PATH) {}
+                    link rel="alternate" type="application/feed+json" title=(feed::title()) href=(feed::JSON_PATH) {}
                     link rel="stylesheet" href="/static/styles.css" {}
                     link href="/static/fonts/inconsolata-semibold.woff2" rel="woff2-font";
                     link href="/static/fonts/inconsolata.woff2" rel="woff2-font";
 
                     @if self.alpine || !self.x_data.is_empty() {
-                        script src=r"https://unpkg.com/alpinejs@3.x.x/dist/cdn.min.js" defer init {}
+                        (assets::ALPINE.script())
                     }
 
                     link rel="stylesheet" href="/static/blog_styles.css" {}
@@ -232,18 +269,20 @@ impl<'src> BaseHtml<'src> {
                         }
                     "#))}
 
+                    @if dev::enabled() {
+                        script {(PreEscaped(dev::client_script()))}
+                    }
+
                     @if self.katex {
-                        link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16.0/dist/katex.min.css" integrity="sha384-Xi8rHCmBmhbuyyhbI88391ZKP2dmfnOl4rT9ZfRI7mLTdk1wblIUnrIq35nqwEvC" crossorigin="anonymous" {}
-                        script defer src=r"https://cdn.jsdelivr.net/npm/katex@0.16.0/dist/katex.min.js" integrity="sha384-X/XCfMm41VSsqRNQgDerQczD69XqmjOOOwYQvr/uuC+j4OPoNhVgjdGFwhvN02Ja" crossorigin="anonymous" {}
-                        script defer src=r"https://cdn.jsdelivr.net/npm/katex@0.16.0/dist/contrib/auto-render.min.js" integrity="sha384-+XBljXPPiv+OzfbB3cVmLHf4hdUFHlWNZN5spNQ7rmHTXpd7WvJum6fIACpNNfIR" crossorigin="anonymous" {}
+                        (assets::KATEX_CSS.stylesheet())
+                        (assets::KATEX_JS.script())
                         script {(PreEscaped(r#"
                             document.addEventListener('DOMContentLoaded', function() {
-                                renderMathInElement(document.body, {
-                                    delimiters: [
-                                        {left: '$$', right: '$$', display: true},
-                                        {left: '$', right: '$', display: false}
-                                    ],
-                                    throwOnError : false
+                                document.querySelectorAll('.math-tex').forEach(function(el) {
+                                    katex.render(el.textContent, el, {
+                                        displayMode: el.dataset.display === 'true',
+                                        throwOnError: false
+                                    });
                                 });
                             });
                         "#))}
@@ -259,7 +298,7 @@ impl<'src> BaseHtml<'src> {
     }
 }
 
-pub fn link(more: &'static str, href: &'static str, children: Markup) -> Markup {
+pub fn link(more: &str, href: &str, children: Markup) -> Markup {
     html! {
         a class=(format!("font-bold hover:text-orange-500 {more}")) href=(href) {
             (children)
@@ -267,34 +306,24 @@ pub fn link(more: &'static str, href: &'static str, children: Markup) -> Markup
     }
 }
 
-fn caption(msg: &'static str) -> Markup {
-    html! {
-        p class="text-sm font-light block text-center pt-1" { (msg) }
-        br{}
-    }
-}
-
-fn image(src: &'static str, cap: &'static str) -> Markup {
-    html! {
-        div style="border-radius: 3pt;" class="bg-white" { img class="p-3" src=(src) {} }
-        (caption(cap))
-    }
-}
-
 fn p(children: Markup) -> Markup {
     html! {
         p .text-justify { (children) }
     }
 }
 
-pub fn tag(name: &'static str, tag: &'static str) -> Markup {
+/// A filter button for the blog search. With `href`, it doubles as a link for visitors
+/// without JavaScript; Alpine cancels the navigation and filters in place instead.
+pub fn tag(name: &str, tag: &str, href: Option<&str>) -> Markup {
+    // A JavaScript string, whatever quotes or backslashes the tag has
+    let name_js = serde_json::to_string(name).expect("a string always serializes");
     html! {
         div class="relative pr-0.5" {
-            button
-                id=(tag) type="button"
+            a
+                id=(tag) href=[href] role="button"
                 class="text-gray-500 text-xs leading-5 font-semibold bg-gray-400/10 rounded-full py-1 px-3 flex items-center dark:bg-gray-950/30 dark:text-gray-400 dark:shadow-highlight/4"
                 x-effect=(format!("
-                    if (hasValue($store.search.text, '{name}')) {{
+                    if (hasValue($store.search.text, {name_js})) {{
                         $el.classList.remove('bg-gray-400/10');
                         $el.classList.remove('dark:bg-gray-950/30');
                         $el.classList.add('bg-gray-800/10');
@@ -307,10 +336,11 @@ pub fn tag(name: &'static str, tag: &'static str) -> Markup {
                     }}
                 "))
                 x-on:click=(format!(
-                    "if (!hasValue($store.search.text, '{name}')) {{
-                        $store.search.update(addWord($store.search.text, '{name}'))
+                    "$event.preventDefault();
+                    if (!hasValue($store.search.text, {name_js})) {{
+                        $store.search.update(addWord($store.search.text, {name_js}))
                     }} else {{
-                        $store.search.update(removeWord($store.search.text, '{name}'))
+                        $store.search.update(removeWord($store.search.text, {name_js}))
                     }}"
                 ))
             {
@@ -320,22 +350,45 @@ pub fn tag(name: &'static str, tag: &'static str) -> Markup {
     }
 }
 
-struct BlogEntryNutshell {
-    href: &'static str,
-    title: &'static str,
-    date: &'static str,
-    des: &'static str,
-    tags: &'static [(&'static str, &'static str)],
+struct BlogEntryNutshell<'src> {
+    slug: &'src str,
+    href: String,
+    title: &'src str,
+    date: String,
+    month_href: String,
+    des: &'src str,
+    tags: &'src [String],
+    reading: reading::Reading,
 }
 
-impl BlogEntryNutshell {
+impl<'src> From<&'src Post> for BlogEntryNutshell<'src> {
+    fn from(post: &'src Post) -> Self {
+        BlogEntryNutshell {
+            slug: &post.slug,
+            href: format!("/blog/{}/", post.slug),
+            title: &post.meta.title,
+            date: post.meta.date.format("%Y-%m-%d").to_string(),
+            month_href: post.meta.date.format("/blog/%Y/%m/").to_string(),
+            des: &post.meta.description,
+            tags: &post.meta.tags,
+            reading: post.reading,
+        }
+    }
+}
+
+impl<'src> BlogEntryNutshell<'src> {
     pub fn render(self) -> Markup {
+        // A JavaScript string, whatever characters the file name has
+        let slug_js = serde_json::to_string(self.slug).expect("a string always serializes");
         html! {
-            div class="flex flex-col" x-show="show_item($el)" {
+            div class="flex flex-col" x-show="show_item($el)" data-slug=(self.slug)
+                x-bind:style=(format!("'order: ' + $store.search.rank({slug_js})"))
+            {
                 div class="flex" {
-                    (tag(self.date, self.date))
-                    @for (name, ntag) in self.tags.iter() {
-                        (tag(name, ntag))
+                    (tag(&self.date, &self.date, Some(&self.month_href)))
+                    @for name in self.tags.iter() {
+                        @let slug = post::tag_slug(name);
+                        (tag(name, &slug, Some(&format!("/blog/tags/{slug}/"))))
                     }
                 }
                 a href=(self.href) {
@@ -343,7 +396,10 @@ impl BlogEntryNutshell {
                         h2 class="blog-title font-bold text-lg pb-2 hover:text-orange-500 text-left" {(self.title)}
                     }
                 }
+                p class="text-sm opacity-70 pb-1" { (self.reading.render()) }
                 a href=(self.href) { p class="text-justify" {(PreEscaped(self.des))} }
+                @let snippet = format!("$store.search.snippet({slug_js})");
+                p class="search-snippet" x-show=(snippet) x-html=(snippet) {}
             }
         }
     }
@@ -381,10 +437,50 @@ fn search_bar() -> Markup {
             document.addEventListener('alpine:init', () => {
                 Alpine.store('search', {
                     text: '',
+                    // Posts the server found for `text`, best first, or null when it was not asked
+                    matches: null,
 
                     update(text) {
                         this.text = text
-                    }
+                    },
+
+                    async query(text) {
+                        if (text.trim() === '') {
+                            this.matches = null
+                            return
+                        }
+                        try {
+                            const response = await fetch(`/api/search?q=${encodeURIComponent(text)}`)
+                            if (!response.ok) throw new Error(response.statusText)
+                            const { results } = await response.json()
+                            // An answer to an older query is of no use anymore
+                            if (this.text === text) this.matches = results
+                        } catch {
+                            // Static exports have no search API, so cards are matched by their text alone
+                            this.matches = null
+                        }
+                    },
+
+                    rank(slug) {
+                        if (this.matches === null) return 0
+                        const rank = this.matches.findIndex(match => match.slug === slug)
+                        return rank === -1 ? this.matches.length : rank
+                    },
+
+                    found(slug) {
+                        return this.matches !== null && this.matches.some(match => match.slug === slug)
+                    },
+
+                    snippet(slug) {
+                        return this.matches?.find(match => match.slug === slug)?.snippet ?? ''
+                    },
+                })
+
+                let pending
+                Alpine.effect(() => {
+                    const text = Alpine.store('search').text
+                    clearTimeout(pending)
+                    pending = setTimeout(() => Alpine.store('search').query(text), 150)
                 })
             })
         "#))}
@@ -413,81 +509,229 @@ fn search_bar() -> Markup {
     }
 }
 
-fn blog() -> Markup {
-    BaseHtml{ title: "Blog - AOx0", alpine: true, children: html!{
+/// A page of post cards that the search bar filters.
+fn post_listing(title: &str, heading: Markup, posts: &[Post]) -> Markup {
+    BaseHtml{ title, alpine: true, children: html!{
             div
                 class="wrapper relative max-w-screen-md container text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100"
                  x-data=(PreEscaped(r#"{
                     show_item(el){
-                        return $store.search.text === '' || hasValue(el.textContent.toLowerCase(), $store.search.text.toLowerCase());
+                        return $store.search.text === ''
+                            || $store.search.found(el.dataset.slug)
+                            || hasValue(el.textContent.toLowerCase(), $store.search.text.toLowerCase());
                     }
                 }"#))
             {
-                div class="flex flex-col space-y-10 md:space-y-0" { h1 {  ("Blog") } }
+                div class="flex flex-col space-y-10 md:space-y-0" { (heading) }
                 div class="flex flex-col gap-5" {
-                    (BlogEntryNutshell { href: "./networking-notes/", title: "[WIP] Networking notes",
-                        tags: &[("Rust", "rust"), ("C", "c"), ("WIP", "wip")],
-                        date: "2023-06-11",
-                        des: "Random notes from the book Network Programming with Rust by Abhishek Chanda, the Guide to Network Programming by Brian Hall, and other sources."
-                    }.render())
-                    (BlogEntryNutshell { href: "./parser-comb-notes/", title: "[WIP] Parser combinator notes",
-                        tags: &[("Rust", "rust"), ("Parser", "parser"), ("WIP", "wip")],
-                        date: "2023-03-16",
-                        des: "Parser combinators are simple, powerful and flexible for building parsers. I explore how to do it with Rust"
-                    }.render())
-                    (BlogEntryNutshell { href: "./type-guidance/", title:"Type guidance on APIs using PhantomData",
-                        tags: &[("Rust", "rust")],
-                        date: "2022-08-06",
-                        des: "In this writeup I learn about PhantomData and how to use it to design unbreakable APIs."
-                    }.render())
-                    (BlogEntryNutshell { href: "./covid/", title: "Data analysis exercise: COVID19 in México",
-                        tags: &[("Mathematica", "mathematica")],
-                        date: "2021-12-25",
-                        des: "A naive examination of open data from México about COVID-19. The purpose, to strengthen my general analysis skills, practicing methods used to produce high-quality media."
-                    }.render())
+                    @for post in posts {
+                        (BlogEntryNutshell::from(post).render())
+                    }
                 }
             }
         }, nav_bar_middle: search_bar(), ..Default::default()}.render()
 }
 
+fn blog(posts: &[Post]) -> Markup {
+    post_listing(
+        &format!("Blog - {}", site::get().author),
+        html! {
+            h1 { ("Blog") }
+            p class="text-sm pb-5" {
+                (link("", "/blog/tags/", html!("Tags")))
+                " · "
+                (link("", "/blog/archive/", html!("Archive")))
+            }
+        },
+        posts,
+    )
+}
+
+fn post_count(count: usize) -> String {
+    match count {
+        1 => "1 post".to_string(),
+        n => format!("{n} posts"),
+    }
+}
+
+fn month_name(month: u32) -> &'static str {
+    u8::try_from(month)
+        .ok()
+        .and_then(|month| chrono::Month::try_from(month).ok())
+        .map_or("Unknown", |month| month.name())
+}
+
+fn archive_index(posts: &[Post]) -> Markup {
+    BaseHtml {
+        title: &format!("Archive - {}", site::get().author),
+        children: html! {
+            div class="max-w-screen-md relative container text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
+                h1 { ("Archive") }
+                @for (year, months) in post::archive(posts).iter().rev() {
+                    h2 class="pt-10" {
+                        (link("", &format!("/blog/{year}/"), html!((year))))
+                        (format!(" ({})", months.values().sum::<usize>()))
+                    }
+                    ul class="list-disc list-inside" {
+                        @for (month, count) in months.iter().rev() {
+                            li {
+                                (link("", &format!("/blog/{year}/{month:02}/"), html!((month_name(*month)))))
+                                (format!(" ({count})"))
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        ..Default::default()
+    }
+    .render()
+}
+
+/// The published posts from `year`, and from `month` of it if given.
+fn archive_page(year: i32, month: Option<u32>, posts: &[Post]) -> Result<Markup, RenderError> {
+    let archive = post::archive(posts);
+    let months = archive.get(&year);
+    let (path, period) = match month {
+        Some(month) => (
+            format!("/blog/{year}/{month:02}/"),
+            format!("{} {year}", month_name(month)),
+        ),
+        None => (format!("/blog/{year}/"), year.to_string()),
+    };
+    let count = match (months, month) {
+        (Some(months), Some(month)) => months.get(&month).copied(),
+        (Some(months), None) => Some(months.values().sum()),
+        (None, _) => None,
+    }
+    .ok_or(RenderError::MissingPage(path))?;
+
+    let matching: Vec<Post> = posts
+        .iter()
+        .filter(|post| {
+            post.meta.date.year() == year && month.is_none_or(|m| post.meta.date.month() == m)
+        })
+        .cloned()
+        .collect();
+
+    Ok(post_listing(
+        &format!("{period} - {}", site::get().author),
+        html! {
+            h1 { "Posts from " (period) }
+            p class="text-sm pb-5" {
+                (format!("{} · ", post_count(count)))
+                @if month.is_none() {
+                    @for (month, count) in months.into_iter().flatten().rev() {
+                        (link("", &format!("/blog/{year}/{month:02}/"), html!((month_name(*month)))))
+                        (format!(" ({count}) · "))
+                    }
+                } @else {
+                    (link("", &format!("/blog/{year}/"), html!((year))))
+                    " · "
+                }
+                (link("", "/blog/archive/", html!("Archive")))
+            }
+        },
+        &matching,
+    ))
+}
+
+/// A four digit year, which `/blog/<year>/` claims over a post slug.
+fn parse_year(segment: &str) -> Option<i32> {
+    (segment.len() == 4 && segment.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| segment.parse().ok())
+        .flatten()
+}
+
+/// A two digit month, `01` to `12`.
+fn parse_month(segment: &str) -> Option<u32> {
+    (segment.len() == 2 && segment.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| segment.parse().ok())
+        .flatten()
+        .filter(|month| (1..=12).contains(month))
+}
+
+fn tag_index(tags: &[post::Tag]) -> Markup {
+    BaseHtml {
+        title: &format!("Tags - {}", site::get().author),
+        children: html! {
+            div class="max-w-screen-md relative container text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
+                h1 { ("Tags") }
+                ul class="list-disc list-inside pt-10" {
+                    @for tag in tags {
+                        li {
+                            (link("", &format!("/blog/tags/{}/", tag.slug), html!((tag.name))))
+                            (format!(" ({})", tag.count))
+                        }
+                    }
+                }
+            }
+        },
+        ..Default::default()
+    }
+    .render()
+}
+
+/// The published posts carrying the tag with the given slug.
+fn tag_page(slug: &str, posts: &[Post]) -> Result<Markup, RenderError> {
+    let tag = post::tags(posts)
+        .into_iter()
+        .find(|tag| tag.slug == slug)
+        .ok_or_else(|| RenderError::MissingPage(format!("/blog/tags/{slug}/")))?;
+    let tagged: Vec<Post> = posts
+        .iter()
+        .filter(|post| post.has_tag(slug))
+        .cloned()
+        .collect();
+
+    Ok(post_listing(
+        &format!("{} - {}", tag.name, site::get().author),
+        html! {
+            h1 { "Posts tagged " (tag.name) }
+            p class="text-sm pb-5" {
+                (format!("{} · ", post_count(tag.count)))
+                (link("", "/blog/tags/", html!("All tags")))
+            }
+        },
+        &tagged,
+    ))
+}
+
 fn welcome() -> Markup {
     html! {
         div class="max-w-screen-md relative container text-center md:text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
             h1 class="text-4xl md:text-5xl font-bold py-10 " { "About Me" }
             p .text-justify {
-                (PreEscaped(r#"
-                    Hi,<br><br>I'm Alejandro Osornio, an enthusiastic programmer who really enjoys compiled
-                    languages, playing around with interpreted ones, and creating side projects of all kinds for
-                    fun.<br><br>I am interested in Cyber-security, computer science, math, and Backend, enjoy writing
-                    Frontend, and like writing CLI tools to make my day-to-day easier.<br><br>Currently, I'm studying
-                    Data Intelligence and Cyber-security at Panamerican University.<br><br>This web page is my blog,
-                    portfolio, and how to contact. Feel free to explore around and to contact me.
-                "#))
+                (PreEscaped(&site::get().about))
             }
         }
     }
 }
 
 fn contact() -> Markup {
-    BaseHtml { title:"Contact - AOx0", children: html!{
-        div class="max-w-screen-md relative container text-left justify-left md:text-left
-            v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
-            h1 { ("Where to find me") }
-            p { "Feel free to reach me out in any of the following places:" }
-            ul class="list-disc list-inside pt-10" {
-                (contact_item("Email", "mailto:aoxo.contact@gmail.com", html!{ "aoxo.contact@gmail.com" }))
-                (contact_item("Github", "https://github.com/AOx0", html!{ "@AOx0" }))
-                (contact_item("Twitter", "https://twitter.com/AlecsOsornio", html!{ "@AlecsOsornio" }))
-                (contact_item("LinkedIn", "https://www.linkedin.com/in/aox0", html!{ "Alejandro Osornio" }))
-                (contact_item("Telegram", "https://t.me/alecz", html!{ "@Alecz" }))
-                (contact_item("Instagram", "https://www.instagram.com/ale.osornio/", html!{ "ale.osornio" }))
-            }
-            p class="text-sm pt-5" { "* I'm most active on Telegram, though." }
-        }
-    }, ..Default::default()}.render()
+    BaseHtml {
+        title: &format!("Contact - {}", site::get().author),
+        children: html! {
+            div class="max-w-screen-md relative container text-left justify-left md:text-left
+                v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
+                h1 { ("Where to find me") }
+                p { "Feel free to reach me out in any of the following places:" }
+                ul class="list-disc list-inside pt-10" {
+                    @for item in &site::get().contact.items {
+                        (contact_item(&item.label, &item.href, html!{ (item.text) }))
+                    }
+                }
+                @if !site::get().contact.note.is_empty() {
+                    p class="text-sm pt-5" { (site::get().contact.note) }
+                }
+            }
+        },
+        ..Default::default()
+    }
+    .render()
 }
 
-fn contact_item(title: &'static str, href: &'static str, children: Markup) -> Markup {
+fn contact_item(title: &str, href: &str, children: Markup) -> Markup {
     html! {
         li {
             (format!("{title}: "))
@@ -496,32 +740,60 @@ fn contact_item(title: &'static str, href: &'static str, children: Markup) -> Ma
     }
 }
 
+fn error_page(status: StatusCode, message: &str) -> Markup {
+    let reason = status.canonical_reason().unwrap_or("Error");
+    BaseHtml {
+        title: &format!("{reason} - {}", site::get().author),
+        children: html! {
+            div class="max-w-screen-md relative container text-center md:text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
+                h1 class="text-4xl md:text-5xl font-bold py-10" { (status.as_u16()) " " (reason) }
+                p .text-justify { (message) }
+                p class="pt-5" { (link("", "/", html!("Go back home"))) }
+            }
+        },
+        ..Default::default()
+    }
+    .render()
+}
+
 fn home() -> Markup {
     BaseHtml {
-        title: "AOx0",
+        title: &site::get().author,
         children: welcome(),
         ..Default::default()
     }
     .render()
 }
 
-fn markdown(file: PathBuf, title: String) -> Markup {
+/// A post body rendered to HTML, along with the headings found on the way.
+struct Rendered {
+    html: String,
+    headings: Vec<toc::Heading>,
+    /// Whether some formula was left for KaTeX to render in the browser.
+    needs_katex: bool,
+}
+
+/// Renders the body of a post to HTML, without the surrounding page.
+fn post_html(post: &Post) -> Result<String, RenderError> {
+    Ok(render_post(post)?.html)
+}
+
+fn render_post(post: &Post) -> Result<Rendered, RenderError> {
     use pulldown_cmark::html;
     use pulldown_cmark::Event;
     use pulldown_cmark::Options;
     use pulldown_cmark::Parser;
     use pulldown_cmark::Tag;
 
-    use std::io::Read;
-
-    let mut file = OpenOptions::new().read(true).open(file).unwrap();
-    let mut input = String::new();
-
-    file.read_to_string(&mut input).unwrap();
-
-    let opts = Options::empty();
+    let extensions = &site::get().markdown;
+    let mut opts = Options::ENABLE_HEADING_ATTRIBUTES;
+    opts.set(Options::ENABLE_TABLES, extensions.tables);
+    opts.set(Options::ENABLE_FOOTNOTES, extensions.footnotes);
+    opts.set(Options::ENABLE_STRIKETHROUGH, extensions.strikethrough);
+    opts.set(Options::ENABLE_TASKLISTS, extensions.tasklists);
+    opts.insert(Options::ENABLE_MATH);
     let mut s = String::new();
-    let p = Parser::new_ext(&input, opts);
+    let mut p: Vec<_> = Parser::new_ext(&post.body, opts).collect();
 
     // We'll build a new vector of events since we can only consume the parser once
     let mut new_p = Vec::new();
@@ -529,103 +801,176 @@ fn markdown(file: PathBuf, title: String) -> Markup {
     let mut to_highlight = String::new();
     // And track a little bit of state
     let mut in_code_block = false;
-    let mut lang = String::from_str("autodetect").unwrap();
+    let mut lang = String::from("txt");
+    // The heading being read, as its start event's index and its text so far
+    let mut heading: Option<(usize, String)> = None;
+    let mut headings = Vec::new();
+    let mut ids = toc::Ids::default();
+    // Footnote bodies are rendered aside and moved to the end of the post
+    let mut footnotes = footnote::Footnotes::default();
+    let mut definition = None;
+    let mut needs_katex = false;
+    // The image being read, as its source, its title and its alt text so far
+    let mut image: Option<(pulldown_cmark::CowStr, pulldown_cmark::CowStr, String)> = None;
+    // Footnote IDs are fixed, and explicit `{#id}`s are taken next so a generated one
+    // never shadows them. An explicit ID used twice is suffixed like a generated one.
+    footnote::reserve(&p, &mut ids);
+    for event in &mut p {
+        if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+            if !ids.reserve(id) {
+                *id = ids.suffixed(id.to_string()).into();
+            }
+        }
+    }
+    // Figures are numbered up front, so a reference can come before its figure
+    let figures = figure::Figures::find(&p, &mut ids);
+    let mut figure = None;
+    // The reading time goes under the title, or first thing when the post has none
+    let mut byline = Some(Event::Html(
+        html! {
+            p class="text-sm opacity-70 pb-4" { (post.reading.render()) }
+        }
+        .into_string()
+        .into(),
+    ));
+    if !matches!(
+        p.first(),
+        Some(Event::Start(Tag::Heading {
+            level: HeadingLevel::H1,
+            ..
+        }))
+    ) {
+        new_p.extend(byline.take());
+    }
+
+    for (i, event) in p.into_iter().enumerate() {
+        // Alt text is plain text, so markup inside it is dropped
+        if let Some((_, _, alt)) = &mut image {
+            match &event {
+                Event::End(TagEnd::Image) => {}
+                Event::Text(text) | Event::Code(text) => {
+                    alt.push_str(text);
+                    continue;
+                }
+                _ => continue,
+            }
+        }
 
-    for event in p {
         match event {
+            Event::Start(Tag::Image {
+                dest_url, title, ..
+            }) => {
+                image = Some((dest_url, title, String::new()));
+            }
+            // An image alone in its paragraph stands in place of the paragraph as a figure
+            Event::Start(Tag::Paragraph) if figures.at(i).is_some() => {
+                figure = figures.at(i);
+            }
+            Event::End(TagEnd::Paragraph) if figure.is_some() => {
+                figure = None;
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((src, title, alt)) = image.take() {
+                    match figure {
+                        Some(figure) => {
+                            let caption = figure::caption(&alt);
+                            let image = images::render(&src, caption, &title)?;
+                            let html = figure::render(figure, image, caption);
+                            new_p.push(Event::Html(html.into_string().into()));
+                        }
+                        None => {
+                            let html = images::render(&src, &alt, &title)?;
+                            new_p.push(Event::InlineHtml(html.into_string().into()));
+                        }
+                    }
+                }
+            }
             Event::Start(Tag::CodeBlock(a)) => {
                 // In actual use you'd probably want to keep track of what language this code is
                 in_code_block = true;
 
                 if let CodeBlockKind::Fenced(a) = a {
-                    lang = String::from_utf8(a.as_bytes().to_owned()).unwrap();
-                    lang = lang.replace("mathematica", "Mathematica");
+                    lang = a.to_string();
                 }
             }
-            Event::Code(a) => {
-                if a.starts_with("lang@") {
-                    lang = a.replace("lang@", "");
-                    lang = lang.split_whitespace().next().unwrap().to_string()
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((new_p.len(), String::new()));
+                new_p.push(event);
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                if let Some((start, text)) = heading.take() {
+                    let Event::Start(Tag::Heading { id: slot, .. }) = &mut new_p[start] else {
+                        unreachable!("headings are recorded at their start event");
+                    };
+                    let id = match slot {
+                        Some(id) => id.to_string(),
+                        None => ids.unique(&text),
+                    };
+                    *slot = Some(id.clone().into());
+                    new_p.push(Event::InlineHtml(toc::permalink(&id).into_string().into()));
+                    headings.push(toc::Heading { level, id, text });
+                }
+                new_p.push(event);
+                if let Some(byline) = byline.take() {
+                    new_p.push(byline);
+                }
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                definition = Some((label, std::mem::take(&mut new_p)));
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some((label, outer)) = definition.take() {
+                    footnotes.define(label, std::mem::replace(&mut new_p, outer));
                 }
+            }
+            Event::FootnoteReference(label) => {
+                let html = footnotes.reference(label);
+                new_p.push(Event::InlineHtml(html.into_string().into()));
+            }
+            Event::InlineMath(ref tex) | Event::DisplayMath(ref tex) => {
+                let display = matches!(event, Event::DisplayMath(_));
+                let html = match math::to_mathml(tex, display) {
+                    Ok(mathml) => mathml,
+                    Err(_) => {
+                        needs_katex = true;
+                        html! {
+                            span class="math-tex" data-display=(display) { (tex) }
+                        }
+                        .into_string()
+                    }
+                };
+                new_p.push(Event::InlineHtml(html.into()));
+            }
+            // Wide tables scroll on their own instead of widening the page
+            Event::Start(Tag::Table(_)) => {
+                new_p.push(Event::Html(r#"<div class="overflow-x-auto">"#.into()));
+                new_p.push(event);
+            }
+            Event::End(TagEnd::Table) => {
+                new_p.push(event);
+                new_p.push(Event::Html("</div>".into()));
+            }
+            Event::Code(a) => {
+                let text = match a.strip_prefix("lang@") {
+                    Some(rest) => {
+                        let (name, code) = rest.split_once(' ').unwrap_or((rest, ""));
+                        lang = name.to_string();
+                        code.trim_start()
+                    }
+                    None => &a,
+                };
 
-                let text = a.trim_start_matches("lang@").trim_start_matches(&lang);
-
-                let mut child = Command::new("chroma")
-                    .args([
-                        &format!(r#"--lexer={}"#, lang),
-                        r#"--style=github-dark"#,
-                        r#"--html"#,
-                        r#"--html-only"#,
-                    ])
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()
-                    .unwrap();
-
-                let child_stdin = child.stdin.as_mut().unwrap();
-                child_stdin.write_all(text.as_bytes()).unwrap();
-                let _ = child_stdin;
-
-                let output = child.wait_with_output().unwrap();
-
-                let mut child = Command::new("ruplacer")
-                    .args([r#"class="([a-zA-Z0-9]+)""#, r#"class="dark:$1 $1""#, r"-"])
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()
-                    .unwrap();
-
-                let child_stdin = child.stdin.as_mut().unwrap();
-                child_stdin.write_all(&output.stdout).unwrap();
-                let _ = child_stdin;
-
-                let output = child.wait_with_output().unwrap();
-
-                let html = String::from_utf8(output.stdout).unwrap();
-                let html = html
-                    .trim()
-                    .trim_start_matches("<pre class=\"dark:chroma chroma\">")
-                    .trim_end_matches("</pre>")
-                    .replace("<code>", "<code class=\"dark:chroma chroma\">")
-                    .replace("class=\"dark:line line\"", "");
+                if let Some((_, heading)) = &mut heading {
+                    heading.push_str(text);
+                }
+                let html = highlight::inline(text, &lang)?;
                 // And put it into the vector
                 new_p.push(Event::InlineHtml(html.into()));
             }
             Event::End(TagEnd::CodeBlock) => {
                 if in_code_block {
                     // Format the whole multi-line code block as HTML all at once
-                    let mut child = Command::new("chroma")
-                        .args([
-                            &format!(r#"--lexer={}"#, lang),
-                            r#"--style=github-dark"#,
-                            r#"--html"#,
-                            r#"--html-only"#,
-                        ])
-                        .stdin(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .spawn()
-                        .unwrap();
-
-                    let child_stdin = child.stdin.as_mut().unwrap();
-                    child_stdin.write_all(to_highlight.as_bytes()).unwrap();
-                    let _ = child_stdin;
-
-                    let output = child.wait_with_output().unwrap();
-
-                    let mut child = Command::new("ruplacer")
-                        .args([r#"class="([a-zA-Z0-9]+)""#, r#"class="dark:$1 $1""#, r"-"])
-                        .stdin(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .spawn()
-                        .unwrap();
-
-                    let child_stdin = child.stdin.as_mut().unwrap();
-                    child_stdin.write_all(&output.stdout).unwrap();
-                    let _ = child_stdin;
-
-                    let output = child.wait_with_output().unwrap();
-
-                    let html = String::from_utf8(output.stdout).unwrap();
+                    let html = highlight::block(&to_highlight, &lang)?;
                     // And put it into the vector
                     new_p.push(Event::Html(html.into()));
                     to_highlight = String::new();
@@ -637,7 +982,13 @@ fn markdown(file: PathBuf, title: String) -> Markup {
                     // If we're in a code block, build up the string of text
                     to_highlight.push_str(&t);
                 } else {
-                    new_p.push(Event::Text(t))
+                    if let Some((_, heading)) = &mut heading {
+                        heading.push_str(&t);
+                    }
+                    match figures.link(&t) {
+                        Some(html) => new_p.push(Event::InlineHtml(html.into_string().into())),
+                        None => new_p.push(Event::Text(t)),
+                    }
                 }
             }
             e => {
@@ -646,49 +997,155 @@ fn markdown(file: PathBuf, title: String) -> Markup {
         }
     }
 
+    if let Some(notes) = footnotes.render() {
+        new_p.push(Event::Html(notes.into_string().into()));
+    }
+
     // Now we send this new vector of events off to be transformed into HTML
     html::push_html(&mut s, new_p.into_iter());
 
-    BaseHtml {
-        title: &format!("{}{} - AOx0", &title.to_uppercase().chars().next().unwrap(), &title[1..].to_owned().replace("-", " ") ),
-        katex: true,
+    Ok(Rendered {
+        html: s,
+        headings,
+        needs_katex,
+    })
+}
+
+fn markdown(post: &Post) -> Result<Markup, RenderError> {
+    let rendered = render_post(post)?;
+    let toc = match post.meta.toc {
+        true => toc::render(&rendered.headings),
+        false => None,
+    };
+
+    Ok(BaseHtml {
+        title: &format!("{} - {}", post.meta.title, site::get().author),
+        katex: rendered.needs_katex,
         blog: true,
         children: html!(
-            div class="max-w-screen-md relative container text-justify md:text-left v-screen mx-auto pt-6 md:py-6 px-10 text-black dark:text-gray-100" {(PreEscaped(s.to_owned()))}
+            div class="xl:flex xl:justify-center" {
+                div class="max-w-screen-md relative container text-justify md:text-left v-screen mx-auto xl:mx-0 pt-6 md:py-6 px-10 text-black dark:text-gray-100" {
+                    @if let Some(toc) = &toc {
+                        details class="xl:hidden mb-6 rounded-lg bg-gray-400/10 dark:bg-gray-950/30 px-5 py-3" {
+                            summary class="cursor-pointer font-semibold" { "Contents" }
+                            div class="pt-3" { (toc) }
+                        }
+                    }
+                    (PreEscaped(rendered.html))
+                }
+                @if let Some(toc) = toc {
+                    aside class="hidden xl:block w-64 shrink-0" {
+                        nav class="sticky top-0 pt-6 md:py-6 text-black dark:text-gray-100" {
+                            p class="font-semibold" { "Contents" }
+                            (toc)
+                        }
+                    }
+                }
+            }
         ),
         ..Default::default()
-    }.render()
+    }.render())
 }
 
 async fn show_contact() -> Markup {
     contact()
 }
 
-async fn show_blog() -> Markup {
-    blog()
+async fn show_blog() -> Result<Response, RenderError> {
+    let posts = post::published()?;
+    let modified = posts.iter().map(Post::modified).max();
+    Ok((conditional::last_modified(modified), blog(&posts)).into_response())
 }
 
-async fn show_blog_entry(Path(name): Path<String>) -> Markup {
-    let file = format!("{}/blog/{}.md", env!("CARGO_MANIFEST_DIR"), name);
-    markdown(PathBuf::from_str(&file).unwrap(), name)
+async fn show_tags() -> Result<Markup, RenderError> {
+    Ok(tag_index(&post::tags(&post::published()?)))
 }
 
-async fn say_hello() -> Markup {
-    home()
+async fn show_tag(Path(tag): Path<String>) -> Result<Markup, RenderError> {
+    tag_page(&tag, &post::published()?)
 }
 
-fn set_return_type<T, F: std::future::Future<Output = T>>(_arg: &F) {}
+async fn show_archive() -> Result<Markup, RenderError> {
+    Ok(archive_index(&post::published()?))
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+async fn show_month(Path((year, month)): Path<(String, String)>) -> Result<Markup, RenderError> {
+    match (parse_year(&year), parse_month(&month)) {
+        (Some(year), Some(month)) => archive_page(year, Some(month), &post::published()?),
+        _ => Err(RenderError::MissingPage(format!("/blog/{year}/{month}/"))),
+    }
+}
+
+async fn show_blog_entry(Path(name): Path<String>) -> Result<Response, RenderError> {
+    if let Some(year) = parse_year(&name) {
+        return Ok(archive_page(year, None, &post::published()?)?.into_response());
+    }
+    let post = post::find(&name)?;
+    let (page, status) = cache::page(&post)?;
+    Ok((
+        [(cache::HEADER, status.as_str())],
+        conditional::last_modified(Some(post.modified())),
+        page,
+    )
+        .into_response())
+}
 
-    // let static_service = axum::error_handling::HandleError::new(
-    //     ,
-    //     handle_error,
-    // );
+type Document = ([(header::HeaderName, &'static str); 1], String);
+
+async fn show_rss() -> Result<Document, RenderError> {
+    let body = feed::rss(&post::published()?)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        body,
+    ))
+}
+
+async fn show_atom() -> Result<Document, RenderError> {
+    let body = feed::atom(&post::published()?)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        body,
+    ))
+}
 
-    let app = Router::new()
+async fn show_json_feed() -> Result<Document, RenderError> {
+    let body = feed::json(&post::published()?)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/feed+json; charset=utf-8")],
+        body,
+    ))
+}
+
+async fn show_search(Query(params): Query<search::Params>) -> Result<Document, RenderError> {
+    let results = search::search(&params.q)?;
+    let body = serde_json::to_string(&results).expect("search results always serialize");
+    Ok((
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        body,
+    ))
+}
+
+async fn show_sitemap() -> Result<Document, RenderError> {
+    let body = sitemap::sitemap(&post::published()?);
+    Ok((
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    ))
+}
+
+async fn show_robots() -> Document {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        sitemap::robots(),
+    )
+}
+
+async fn say_hello() -> Markup {
+    home()
+}
+
+fn app() -> Router {
+    let router = Router::new()
         .route(
             "/favicon.ico",
             get(|| async { Redirect::permanent("/static/favicon.ico") }),
@@ -697,92 +1154,410 @@ async fn main() -> Result<()> {
         .route("/contact/", get(show_contact))
         .route("/blog/:name/", get(show_blog_entry))
         .route("/blog/", get(show_blog))
+        .route("/blog/tags/", get(show_tags))
+        .route("/blog/tags/:tag/", get(show_tag))
+        .route("/blog/archive/", get(show_archive))
+        .route("/blog/:year/:month/", get(show_month))
+        .route(feed::RSS_PATH, get(show_rss))
+        .route(feed::ATOM_PATH, get(show_atom))
+        .route(feed::JSON_PATH, get(show_json_feed))
+        .route(sitemap::SITEMAP_PATH, get(show_sitemap))
+        .route(sitemap::ROBOTS_PATH, get(show_robots))
+        .route(search::PATH, get(show_search))
+        // Static files have their own validators, and the live reload stream never ends
+        .layer(axum::middleware::from_fn(conditional::middleware))
+        .nest_service(
+            images::ROUTE,
+            get_service(
+                tower_http::services::ServeDir::new(images::cache_dir())
+                    .not_found_service(handle_error.into_service()),
+            ),
+        )
         .nest_service(
             "/static/",
-            get_service(tower_http::services::ServeDir::new("./static")),
-        );
-    // .nest_service("/static/", static_service);
-
-    let port = "8000";
-
-    let listener = TcpListener::bind(format!("0.0.0.0:{port}")).await.unwrap();
-    if args.len() == 1 {
-        let (txs, rxs) = tokio::sync::oneshot::channel::<()>();
-
-        let renderer = async move {
-            let current = format!("{}/target", std::env!("CARGO_MANIFEST_DIR"));
-            let out_dir = format!("{current}/0.0.0.0");
-
-            if PathBuf::from(&out_dir).exists() {
-                println!("Removing old {out_dir}");
-                remove_dir_all(&out_dir).await?;
-            }
-            println!("Executing commands");
-
-            println!("    Suckit");
-            Command::new("suckit")
-                .args(format!("http://0.0.0.0:{port}/ -j 8 -o {current}",).split_whitespace())
-                .status()?;
-            println!("    Replace index.html -> ./");
-            Command::new("ruplacer")
-                .args(format!("index.html ./ {out_dir} --quiet --go").split_whitespace())
-                .status()?;
-
-            println!("    Replace ../url.algo -> https://url.algo");
-            Command::new("ruplacer")
-                .args(
-                    format!(
-                        r#"(\.\./)+([a-zA-Z\-\.]+)(\.com|\.me|\.net|\.org|\.mx)/ https://$2$3/ {out_dir} --quiet --go"#
-                    )
-                    .split_whitespace(),
-                )
-                .status()?;
-
-            println!("    Replace index_no_slash.html -> ");
-            Command::new("ruplacer")
-                .args(["index_no_slash.html", "", &out_dir, "--quiet", "--go"])
-                .status()?;
-
-            println!("    Replace \"/./\" -> \"/\"");
-            Command::new("ruplacer")
-                .args([r#"/\./""#, r#"/""#, &out_dir, "--quiet", "--go"])
-                .status()?;
-
-            println!("    Replace syntax-something -> dark:syntax-something syntax-something");
-            Command::new("ruplacer")
-                .args([
-                    r#"syntax-([a-zA-Z]+)"#,
-                    "$0 dark:$0",
-                    &out_dir,
-                    "-t",
-                    "*.html",
-                    "--quiet",
-                    "--go",
-                ])
-                .status()?;
-            txs.send(()).unwrap();
-            Ok(())
+            get_service(
+                tower_http::services::ServeDir::new("./static")
+                    .precompressed_br()
+                    .precompressed_gzip()
+                    .not_found_service(handle_error.into_service()),
+            ),
+        )
+        .fallback(handle_error);
+
+    let router = if dev::enabled() {
+        router.route(dev::ENDPOINT, get(dev::events))
+    } else {
+        router
+    };
+
+    // The default predicate leaves images, tiny bodies and the live reload stream alone
+    router.layer(tower_http::compression::CompressionLayer::new())
+}
+
+async fn serve(host: Option<String>, port: Option<u16>, watch: bool) -> Result<()> {
+    // Kept alive for as long as the server runs
+    let _watcher = if watch {
+        println!("Watching for changes");
+        Some(dev::watch()?)
+    } else {
+        None
+    };
+
+    // Built now so the first search does not wait for it
+    search::index()?;
+    // Pages show the original images until their variants are ready
+    tokio::task::spawn_blocking(images::refresh);
+    let app = app();
+
+    let site = site::get();
+    let host = host.unwrap_or_else(|| site.host.clone());
+    let port = port.unwrap_or(site.port);
+    let listener = TcpListener::bind((host.as_str(), port))
+        .await
+        .with_context(|| format!("failed to listen on {host}:{port}"))?;
+    println!("Listening on http://{host}:{port}");
+    axum::serve(listener, app.into_make_service()).await?;
+    Ok(())
+}
+
+async fn run(cli: Cli) -> Result<ExitCode> {
+    site::init(&cli.config)?;
+
+    match cli.command {
+        Command::Serve { host, port, watch } => serve(host, port, watch).await?,
+        Command::Build { out_dir } => {
+            println!("Exporting site to {}", out_dir.display());
+            tokio::task::spawn_blocking(move || export::export(&out_dir)).await??;
+        }
+        Command::New { slug, title } => {
+            let path = cli::new_post(&slug, title)?;
+            println!("Created {}", path.display());
+        }
+        Command::Check => return cli::check(),
+        Command::Vendor => {
+            println!("Vendoring assets into {}", assets::vendor_dir().display());
+            assets::vendor()?;
+        }
+        Command::BuildCss => css::build()?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use http::Request;
+    use tower::ServiceExt;
+
+    async fn request(uri: &str) -> (StatusCode, String) {
+        let response = app()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    async fn conditional(uri: &str, header: header::HeaderName, value: &str) -> StatusCode {
+        let request = Request::get(uri)
+            .header(header, value)
+            .body(Body::empty())
+            .unwrap();
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    async fn validators(uri: &str) -> (String, Option<String>) {
+        let response = app()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            Some(value.to_str().unwrap().to_string())
         };
-        set_return_type::<Result<()>, _>(&renderer);
+        (header(header::ETAG).unwrap(), header(header::LAST_MODIFIED))
+    }
 
-        let a = tokio::spawn(renderer);
+    #[tokio::test]
+    async fn known_post_is_served() {
+        let (status, body) = request("/blog/type-guidance/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("PhantomData"));
+    }
+
+    #[tokio::test]
+    async fn hostile_post_paths_are_not_found() {
+        for uri in [
+            "/blog/..%2F..%2FCargo.toml/",
+            "/blog/..%2FCargo/",
+            "/blog/%2e%2e%2fCargo/",
+            "/blog/..%5C..%5CCargo/",
+            "/blog/%2Fetc%2Fpasswd/",
+            "/blog/../Cargo.toml/",
+            "/blog/..%252FCargo/",
+            "/blog/covid%00/",
+            "/blog/covid.md/",
+            "/blog/Covid/",
+            "/blog/.covid/",
+            "/blog/-covid/",
+        ] {
+            let (status, body) = request(uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+            assert!(!body.contains("[package]"), "{uri} leaked Cargo.toml");
+        }
+    }
 
-        let server = axum::serve(listener, app.into_make_service());
+    #[tokio::test]
+    async fn pages_carry_validators() {
+        for uri in ["/blog/", "/blog/covid/"] {
+            let (etag, modified) = validators(uri).await;
+            assert!(
+                etag.starts_with("W/\"") && etag.ends_with('"'),
+                "{uri}: {etag}"
+            );
+            assert!(modified.is_some(), "{uri} has no Last-Modified");
+        }
+        let (_, modified) = validators("/contact/").await;
+        assert!(modified.is_none());
+    }
 
-        let graceful = server.with_graceful_shutdown(async move {
-            println!("Starting Axum Server");
-            rxs.await.ok();
-            println!("Ending Axum Server");
-        });
+    #[tokio::test]
+    async fn matching_etag_is_not_modified() {
+        for uri in ["/blog/", "/contact/", "/blog/covid/"] {
+            let (etag, _) = validators(uri).await;
+            let status = conditional(uri, header::IF_NONE_MATCH, &etag).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{uri}");
+            let strong = etag.trim_start_matches("W/");
+            let status = conditional(uri, header::IF_NONE_MATCH, strong).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{uri}");
+            let status = conditional(uri, header::IF_NONE_MATCH, r#""stale""#).await;
+            assert_eq!(status, StatusCode::OK, "{uri}");
+        }
 
-        graceful.await?;
-        println!("Axum Server Ended");
-        a.await??;
-        Ok(())
-    } else {
-        axum::serve(listener, app.into_make_service())
+        let (etag, _) = validators("/blog/covid/").await;
+        let response = app()
+            .oneshot(
+                Request::get("/blog/covid/")
+                    .header(header::IF_NONE_MATCH, etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(response.headers().contains_key(header::ETAG));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unchanged_since_is_not_modified() {
+        for uri in ["/blog/", "/blog/covid/"] {
+            let (_, modified) = validators(uri).await;
+            let modified = modified.unwrap();
+            let status = conditional(uri, header::IF_MODIFIED_SINCE, &modified).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{uri}");
+            let old = "Sat, 01 Jan 2000 00:00:00 GMT";
+            let status = conditional(uri, header::IF_MODIFIED_SINCE, old).await;
+            assert_eq!(status, StatusCode::OK, "{uri}");
+        }
+
+        // Pages without a Last-Modified can only be validated by their ETag
+        let now = "Fri, 01 Jan 2100 00:00:00 GMT";
+        let status = conditional("/contact/", header::IF_MODIFIED_SINCE, now).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn if_none_match_wins_over_if_modified_since() {
+        let (_, modified) = validators("/blog/covid/").await;
+        let request = Request::get("/blog/covid/")
+            .header(header::IF_NONE_MATCH, r#""stale""#)
+            .header(header::IF_MODIFIED_SINCE, modified.unwrap())
+            .body(Body::empty())
+            .unwrap();
+        let status = app().oneshot(request).await.unwrap().status();
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn figures_are_numbered_and_referenced() {
+        let (status, body) = request("/blog/covid/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"<figure id="fig-ingresos""#));
+        assert!(body.contains("Figure 1: Daily people registration over time</figcaption>"));
+        assert!(body.contains(r##"<a href="#fig-totalvsconf">Figure 3</a>"##));
+        assert!(!body.contains("@fig:"));
+    }
+
+    #[tokio::test]
+    async fn cards_show_reading_time() {
+        let (_, body) = request("/blog/").await;
+        assert!(body.contains(" min read · "));
+    }
+
+    #[tokio::test]
+    async fn search_ranks_posts_by_their_body() {
+        let (status, body) = request("/api/search?q=mexico").await;
+        assert_eq!(status, StatusCode::OK);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let first = &response["results"][0];
+        assert_eq!(first["slug"], "covid");
+        assert!(first["snippet"]
+            .as_str()
+            .unwrap()
+            .contains("<mark>México</mark>"));
+
+        // Stemmed, so the plural finds the singular
+        let (_, body) = request("/api/search?q=parsers%20combinators").await;
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["results"][0]["slug"], "parser-comb-notes");
+
+        let (_, body) = request("/api/search?q=").await;
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["results"].as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn archive_lists_posts_by_period() {
+        let (status, body) = request("/blog/archive/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"href="/blog/2023/""#));
+        assert!(body.contains(r#"href="/blog/2023/06/""#));
+
+        let (status, body) = request("/blog/2023/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("/blog/networking-notes/"));
+        assert!(body.contains("/blog/parser-comb-notes/"));
+        assert!(!body.contains("/blog/covid/"));
+
+        let (status, body) = request("/blog/2023/06/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("/blog/networking-notes/"));
+        assert!(!body.contains("/blog/parser-comb-notes/"));
+
+        for uri in [
+            "/blog/1999/",
+            "/blog/2023/01/",
+            "/blog/2023/13/",
+            "/blog/2023/6/",
+        ] {
+            let (status, _) = request(uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_are_compressed_when_accepted() {
+        for encoding in ["gzip", "br"] {
+            let request = Request::get("/blog/covid/")
+                .header(header::ACCEPT_ENCODING, encoding)
+                .body(Body::empty())
+                .unwrap();
+            let response = app().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_ENCODING], encoding);
+        }
+
+        let response = app()
+            .oneshot(Request::get("/blog/covid/").body(Body::empty()).unwrap())
             .await
             .unwrap();
-        Ok(())
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    }
+
+    #[tokio::test]
+    async fn rendered_posts_are_cached() {
+        // No other test renders this post, so the first request renders it
+        let uri = "/blog/parser-comb-notes/";
+        for expected in ["miss", "hit"] {
+            let response = app()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[cache::HEADER], expected);
+        }
+
+        // An edited source is rendered again
+        let mut post = post::find("parser-comb-notes").unwrap();
+        post.hash = post.hash.wrapping_add(1);
+        assert_eq!(cache::page(&post).unwrap().1, cache::Status::Miss);
+        assert_eq!(cache::page(&post).unwrap().1, cache::Status::Hit);
+    }
+
+    #[test]
+    fn heading_ids_never_repeat() {
+        let mut post = post::find("covid").unwrap();
+        post.body =
+            "# Fn 1\n\nText[^a].\n\n## Intro {#intro}\n\n## Again {#intro}\n\n[^a]: Note.\n"
+                .to_string();
+        let html = post_html(&post).unwrap();
+        for id in ["fn-1", "fn-1-1", "fnref-1", "intro", "intro-1"] {
+            let attribute = format!(r#"id="{id}""#);
+            assert_eq!(html.matches(&attribute).count(), 1, "{id} in {html}");
+        }
+    }
+
+    #[test]
+    fn export_leaves_other_directories_alone() {
+        let dir = std::env::temp_dir().join(format!("export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "mine").unwrap();
+
+        let result = export::export(&dir);
+        let kept = dir.join("notes.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+        assert!(kept);
+    }
+
+    #[test]
+    fn tag_names_are_escaped_in_scripts() {
+        let html = tag("it's \\ \"C#\"", "x", None).into_string();
+        let script = r#"hasValue($store.search.text, &quot;it's \\ \&quot;C#\&quot;&quot;)"#;
+        assert!(html.contains(script), "{html}");
+    }
+
+    #[test]
+    fn card_slugs_are_escaped_in_scripts() {
+        let mut post = post::find("covid").unwrap();
+        post.slug = "it's".to_string();
+        let html = BlogEntryNutshell::from(&post).render().into_string();
+        assert!(
+            html.contains("$store.search.rank(&quot;it's&quot;)"),
+            "{html}"
+        );
+        assert!(
+            html.contains("$store.search.snippet(&quot;it's&quot;)"),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn slugs_are_validated() {
+        assert!(post::is_valid_slug("networking-notes"));
+        assert!(post::is_valid_slug("covid19"));
+        for slug in [
+            "", "..", "a/b", "a\\b", "a--b", "-a", "a-", "A", "a.md", "a b", "2024",
+        ] {
+            assert!(!post::is_valid_slug(slug), "{slug:?}");
+        }
     }
 }
@@ -0,0 +1,603 @@
+//! LaTeX math to MathML, for the `$...$` and `$$...$$` spans in posts.
+//!
+//! Only the common subset of LaTeX that posts use is understood: scripts, fractions,
+//! roots, accents, fences, Greek letters, functions and the usual operators. Anything
+//! else is reported as unsupported, and `render_post` leaves that expression for KaTeX
+//! to render in the browser instead.
+
+use crate::feed::escape_xml;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Unsupported(String);
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, Unsupported>;
+
+fn unsupported<T>(what: impl Into<String>) -> Result<T> {
+    Err(Unsupported(what.into()))
+}
+
+const GREEK: &[(&str, char)] = &[
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ϵ'),
+    ("varepsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("vartheta", 'ϑ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("pi", 'π'),
+    ("rho", 'ρ'),
+    ("sigma", 'σ'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'ϕ'),
+    ("varphi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("Gamma", 'Γ'),
+    ("Delta", 'Δ'),
+    ("Theta", 'Θ'),
+    ("Lambda", 'Λ'),
+    ("Xi", 'Ξ'),
+    ("Pi", 'Π'),
+    ("Sigma", 'Σ'),
+    ("Upsilon", 'Υ'),
+    ("Phi", 'Φ'),
+    ("Psi", 'Ψ'),
+    ("Omega", 'Ω'),
+];
+
+/// Symbols that behave like identifiers rather than operators.
+const IDENTIFIERS: &[(&str, char)] = &[
+    ("infty", '∞'),
+    ("partial", '∂'),
+    ("nabla", '∇'),
+    ("emptyset", '∅'),
+    ("ell", 'ℓ'),
+    ("hbar", 'ℏ'),
+];
+
+const OPERATORS: &[(&str, char)] = &[
+    ("cdot", '⋅'),
+    ("times", '×'),
+    ("div", '÷'),
+    ("pm", '±'),
+    ("mp", '∓'),
+    ("ast", '∗'),
+    ("circ", '∘'),
+    ("le", '≤'),
+    ("leq", '≤'),
+    ("ge", '≥'),
+    ("geq", '≥'),
+    ("ne", '≠'),
+    ("neq", '≠'),
+    ("approx", '≈'),
+    ("equiv", '≡'),
+    ("sim", '∼'),
+    ("propto", '∝'),
+    ("to", '→'),
+    ("rightarrow", '→'),
+    ("leftarrow", '←'),
+    ("gets", '←'),
+    ("mapsto", '↦'),
+    ("Rightarrow", '⇒'),
+    ("Leftarrow", '⇐'),
+    ("Leftrightarrow", '⇔'),
+    ("implies", '⟹'),
+    ("iff", '⟺'),
+    ("in", '∈'),
+    ("notin", '∉'),
+    ("ni", '∋'),
+    ("subset", '⊂'),
+    ("subseteq", '⊆'),
+    ("supset", '⊃'),
+    ("supseteq", '⊇'),
+    ("cup", '∪'),
+    ("cap", '∩'),
+    ("setminus", '∖'),
+    ("forall", '∀'),
+    ("exists", '∃'),
+    ("neg", '¬'),
+    ("lnot", '¬'),
+    ("land", '∧'),
+    ("wedge", '∧'),
+    ("lor", '∨'),
+    ("vee", '∨'),
+    ("oplus", '⊕'),
+    ("otimes", '⊗'),
+    ("mid", '∣'),
+    ("ldots", '…'),
+    ("dots", '…'),
+    ("cdots", '⋯'),
+    ("vdots", '⋮'),
+    ("ddots", '⋱'),
+    ("langle", '⟨'),
+    ("rangle", '⟩'),
+    ("lfloor", '⌊'),
+    ("rfloor", '⌋'),
+    ("lceil", '⌈'),
+    ("rceil", '⌉'),
+    ("vert", '|'),
+    ("Vert", '‖'),
+    ("{", '{'),
+    ("}", '}'),
+    ("|", '‖'),
+];
+
+/// Operators whose scripts go above and below them in display math.
+const LARGE_OPERATORS: &[(&str, char)] = &[
+    ("sum", '∑'),
+    ("prod", '∏'),
+    ("coprod", '∐'),
+    ("bigcup", '⋃'),
+    ("bigcap", '⋂'),
+    ("bigoplus", '⨁'),
+    ("bigotimes", '⨂'),
+];
+
+/// Integrals keep their limits to the side, as in LaTeX.
+const INTEGRALS: &[(&str, char)] = &[("int", '∫'), ("iint", '∬'), ("iiint", '∭'), ("oint", '∮')];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "sec", "csc", "cot", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "deg", "det", "dim", "ker", "gcd", "arg", "hom", "Pr",
+];
+
+/// Functions whose subscripts go below them in display math.
+const LIMIT_FUNCTIONS: &[&str] = &["lim", "max", "min", "sup", "inf", "liminf", "limsup"];
+
+const ACCENTS: &[(&str, char)] = &[
+    ("hat", '^'),
+    ("widehat", '^'),
+    ("bar", '¯'),
+    ("overline", '‾'),
+    ("vec", '→'),
+    ("tilde", '~'),
+    ("widetilde", '~'),
+    ("dot", '˙'),
+    ("ddot", '¨'),
+];
+
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.1667em"),
+    (":", "0.2222em"),
+    (">", "0.2222em"),
+    (";", "0.2778em"),
+    (" ", "0.3333em"),
+    ("quad", "1em"),
+    ("qquad", "2em"),
+];
+
+const DOUBLE_STRUCK: &[(char, char)] = &[
+    ('C', 'ℂ'),
+    ('H', 'ℍ'),
+    ('N', 'ℕ'),
+    ('P', 'ℙ'),
+    ('Q', 'ℚ'),
+    ('R', 'ℝ'),
+    ('Z', 'ℤ'),
+];
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(key, _)| *key == name).map(|(_, v)| *v)
+}
+
+/// A piece of the formula, already rendered.
+struct Node {
+    mathml: String,
+    /// Whether scripts attached to it go above and below in display math.
+    limits: bool,
+}
+
+impl Node {
+    fn new(mathml: String) -> Self {
+        Node {
+            mathml,
+            limits: false,
+        }
+    }
+
+    fn mi(text: &str) -> Self {
+        Node::new(format!("<mi>{}</mi>", escape_xml(text)))
+    }
+
+    fn mo(text: &str) -> Self {
+        Node::new(format!("<mo>{}</mo>", escape_xml(text)))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    display: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// A sequence of nodes up to `close`, or up to the end of the input without one.
+    fn row(&mut self, close: Option<char>) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if close.is_some() => return unsupported("unbalanced braces"),
+                None => return Ok(nodes),
+                Some(c) if Some(c) == close => {
+                    self.pos += 1;
+                    return Ok(nodes);
+                }
+                Some('}') => return unsupported("unbalanced braces"),
+                Some('&') => return unsupported("alignment with `&`"),
+                _ => {
+                    let atom = self.atom(false)?;
+                    nodes.push(self.scripts(atom)?);
+                }
+            }
+        }
+    }
+
+    fn group(nodes: Vec<Node>) -> Node {
+        match <[Node; 1]>::try_from(nodes) {
+            Ok([node]) => node,
+            Err(nodes) => Node::new(format!(
+                "<mrow>{}</mrow>",
+                nodes.into_iter().map(|n| n.mathml).collect::<String>()
+            )),
+        }
+    }
+
+    /// A single token, group or command. Inside scripts a number is a single digit, so
+    /// `x^23` is `x²3` like in LaTeX.
+    fn atom(&mut self, script: bool) -> Result<Node> {
+        self.skip_whitespace();
+        let Some(c) = self.peek() else {
+            return unsupported("missing argument");
+        };
+
+        match c {
+            // A script with nothing before it attaches to an empty base
+            '^' | '_' if !script => return Ok(Node::new("<mrow></mrow>".to_string())),
+            '^' | '_' => return unsupported("nested scripts without braces"),
+            '}' | '&' => return unsupported(format!("unexpected `{c}`")),
+            _ => {}
+        }
+        self.pos += 1;
+
+        Ok(match c {
+            '{' => Parser::group(self.row(Some('}'))?),
+            '\\' => self.command()?,
+            '0'..='9' if script => Node::new(format!("<mn>{c}</mn>")),
+            '0'..='9' => {
+                let mut number = c.to_string();
+                while let Some(c) = self.peek() {
+                    let decimal = c == '.'
+                        && self
+                            .chars
+                            .get(self.pos + 1)
+                            .is_some_and(char::is_ascii_digit);
+                    if !(c.is_ascii_digit() || decimal) {
+                        break;
+                    }
+                    number.push(c);
+                    self.pos += 1;
+                }
+                Node::new(format!("<mn>{number}</mn>"))
+            }
+            '-' => Node::mo("−"),
+            '*' => Node::mo("∗"),
+            '\'' => Node::mo("′"),
+            c if c.is_alphabetic() => Node::mi(&c.to_string()),
+            c => Node::mo(&c.to_string()),
+        })
+    }
+
+    /// Attaches any `^` and `_` following `base`.
+    fn scripts(&mut self, base: Node) -> Result<Node> {
+        let (mut sup, mut sub) = (None, None);
+        loop {
+            self.skip_whitespace();
+            let slot = match self.peek() {
+                Some('^') => &mut sup,
+                Some('_') => &mut sub,
+                _ => break,
+            };
+            if slot.is_some() {
+                return unsupported("double script");
+            }
+            self.pos += 1;
+            *slot = Some(self.atom(true)?.mathml);
+        }
+
+        let (under, over, both) = match base.limits && self.display {
+            true => ("munder", "mover", "munderover"),
+            false => ("msub", "msup", "msubsup"),
+        };
+        let base = base.mathml;
+        Ok(Node::new(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<{under}>{base}{sub}</{under}>"),
+            (None, Some(sup)) => format!("<{over}>{base}{sup}</{over}>"),
+            (Some(sub), Some(sup)) => format!("<{both}>{base}{sub}{sup}</{both}>"),
+        }))
+    }
+
+    /// The raw contents of a `{...}` argument, for commands taking text.
+    fn text_argument(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        if self.next() != Some('{') {
+            return unsupported("text argument without braces");
+        }
+        let mut depth = 0;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                None => return unsupported("unbalanced braces"),
+                Some('}') if depth == 0 => return Ok(text),
+                Some(c) => {
+                    depth += match c {
+                        '{' => 1,
+                        '}' => -1,
+                        _ => 0,
+                    };
+                    text.push(c);
+                }
+            }
+        }
+    }
+
+    /// The delimiter after `\left` or `\right`.
+    fn delimiter(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let delimiter = match self.next() {
+            Some('\\') => {
+                let name = self.command_name();
+                match lookup(OPERATORS, &name) {
+                    Some(c) => c.to_string(),
+                    None => return unsupported(format!("delimiter `\\{name}`")),
+                }
+            }
+            Some('.') => return Ok(String::new()),
+            Some(c @ ('(' | ')' | '[' | ']' | '|' | '/')) => c.to_string(),
+            _ => return unsupported("missing delimiter"),
+        };
+        Ok(format!(
+            r#"<mo fence="true" stretchy="true">{}</mo>"#,
+            escape_xml(&delimiter)
+        ))
+    }
+
+    fn command_name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            name.push(c);
+            self.pos += 1;
+        }
+        if name.is_empty() {
+            if let Some(c) = self.next() {
+                name.push(c);
+            }
+        }
+        name
+    }
+
+    fn command(&mut self) -> Result<Node> {
+        let name = self.command_name();
+        let name = name.as_str();
+
+        if let Some(c) = lookup(GREEK, name) {
+            // Capital Greek letters are upright, like in LaTeX
+            return Ok(match c.is_uppercase() {
+                true => Node::new(format!(r#"<mi mathvariant="normal">{c}</mi>"#)),
+                false => Node::mi(&c.to_string()),
+            });
+        }
+        if let Some(c) = lookup(IDENTIFIERS, name) {
+            return Ok(Node::mi(&c.to_string()));
+        }
+        if let Some(c) = lookup(OPERATORS, name) {
+            return Ok(Node::mo(&c.to_string()));
+        }
+        if let Some(c) = lookup(LARGE_OPERATORS, name) {
+            return Ok(Node {
+                mathml: format!(r#"<mo largeop="true" movablelimits="true">{c}</mo>"#),
+                limits: true,
+            });
+        }
+        if let Some(c) = lookup(INTEGRALS, name) {
+            return Ok(Node::new(format!(r#"<mo largeop="true">{c}</mo>"#)));
+        }
+        if FUNCTIONS.contains(&name) {
+            return Ok(Node::mi(name));
+        }
+        if LIMIT_FUNCTIONS.contains(&name) {
+            return Ok(Node {
+                mathml: format!("<mo movablelimits=\"true\">{name}</mo>"),
+                limits: true,
+            });
+        }
+        if let Some(width) = lookup(SPACES, name) {
+            return Ok(Node::new(format!(r#"<mspace width="{width}"/>"#)));
+        }
+        if let Some(accent) = lookup(ACCENTS, name) {
+            let base = self.atom(true)?.mathml;
+            return Ok(Node::new(format!(
+                r#"<mover accent="true">{base}<mo stretchy="true">{accent}</mo></mover>"#
+            )));
+        }
+
+        Ok(match name {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.atom(true)?.mathml;
+                let denominator = self.atom(true)?.mathml;
+                Node::new(format!("<mfrac>{numerator}{denominator}</mfrac>"))
+            }
+            "binom" => {
+                let top = self.atom(true)?.mathml;
+                let bottom = self.atom(true)?.mathml;
+                Node::new(format!(
+                    r#"<mrow><mo>(</mo><mfrac linethickness="0">{top}{bottom}</mfrac><mo>)</mo></mrow>"#
+                ))
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let index = Parser::group(self.row(Some(']'))?).mathml;
+                    let radicand = self.atom(true)?.mathml;
+                    Node::new(format!("<mroot>{radicand}{index}</mroot>"))
+                } else {
+                    let radicand = self.atom(true)?.mathml;
+                    Node::new(format!("<msqrt>{radicand}</msqrt>"))
+                }
+            }
+            "text" | "textrm" | "mbox" => Node::new(format!(
+                "<mtext>{}</mtext>",
+                escape_xml(&self.text_argument()?)
+            )),
+            "mathrm" | "operatorname" => Node::new(format!(
+                r#"<mi mathvariant="normal">{}</mi>"#,
+                escape_xml(&self.text_argument()?)
+            )),
+            "mathbf" => Node::new(format!(
+                r#"<mi mathvariant="bold">{}</mi>"#,
+                escape_xml(&self.text_argument()?)
+            )),
+            "mathbb" => {
+                let text = self.text_argument()?;
+                let letters = text
+                    .trim()
+                    .chars()
+                    .map(|c| DOUBLE_STRUCK.iter().find(|(k, _)| *k == c).map(|(_, v)| *v))
+                    .collect::<Option<String>>();
+                match letters {
+                    Some(letters) if !letters.is_empty() => Node::mi(&letters),
+                    _ => return unsupported(format!("`\\mathbb{{{text}}}`")),
+                }
+            }
+            "left" | "right" => Node::new(self.delimiter()?),
+            "!" => Node::new(r#"<mspace width="-0.1667em"/>"#.to_string()),
+            "displaystyle" | "textstyle" | "limits" | "nolimits" => {
+                Node::new("<mrow></mrow>".to_string())
+            }
+            name => return unsupported(format!("`\\{name}`")),
+        })
+    }
+}
+
+/// Converts a formula to a `<math>` element, keeping the source as an annotation so it
+/// can still be copied.
+pub fn to_mathml(tex: &str, display: bool) -> Result<String> {
+    let mut parser = Parser {
+        chars: tex.chars().collect(),
+        pos: 0,
+        display,
+    };
+    let body = parser.row(None)?;
+
+    Ok(format!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="{}"><semantics><mrow>{}</mrow><annotation encoding="application/x-tex">{}</annotation></semantics></math>"#,
+        if display { "block" } else { "inline" },
+        body.into_iter().map(|n| n.mathml).collect::<String>(),
+        escape_xml(tex.trim()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MathML for `tex` inside the `<math>` wrapper.
+    fn body(tex: &str, display: bool) -> String {
+        let mathml = to_mathml(tex, display).unwrap();
+        let start = mathml.find("<semantics><mrow>").unwrap() + "<semantics><mrow>".len();
+        let end = mathml.rfind("</mrow><annotation").unwrap();
+        mathml[start..end].to_string()
+    }
+
+    fn fails(tex: &str) -> String {
+        to_mathml(tex, false).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(body("x^2", false), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            body("a_{ij}^n", false),
+            "<msubsup><mi>a</mi><mrow><mi>i</mi><mi>j</mi></mrow><mi>n</mi></msubsup>"
+        );
+        assert!(body(r"\sum_{i=1}^n i", true).starts_with("<munderover>"));
+        assert!(body(r"\sum_{i=1}^n i", false).starts_with("<msubsup>"));
+    }
+
+    #[test]
+    fn fractions_and_roots() {
+        assert_eq!(
+            body(r"\frac{1}{2}", false),
+            "<mfrac><mn>1</mn><mn>2</mn></mfrac>"
+        );
+        assert_eq!(body(r"\sqrt{x}", false), "<msqrt><mi>x</mi></msqrt>");
+        assert_eq!(
+            body(r"\sqrt[3]{x}", false),
+            "<mroot><mi>x</mi><mn>3</mn></mroot>"
+        );
+    }
+
+    #[test]
+    fn fences_and_double_struck() {
+        assert_eq!(
+            body(r"\left( x \right)", false),
+            r#"<mo fence="true" stretchy="true">(</mo><mi>x</mi><mo fence="true" stretchy="true">)</mo>"#
+        );
+        assert_eq!(body(r"\mathbb{R}", false), "<mi>ℝ</mi>");
+    }
+
+    #[test]
+    fn source_is_kept_as_annotation() {
+        let mathml = to_mathml("a < b", true).unwrap();
+        assert!(mathml.contains(r#"display="block""#));
+        assert!(
+            mathml.contains(r#"<annotation encoding="application/x-tex">a &lt; b</annotation>"#)
+        );
+    }
+
+    #[test]
+    fn unsupported_input_is_reported() {
+        assert_eq!(fails("a & b"), "alignment with `&`");
+        assert_eq!(fails(r"a \\ b"), r"`\\`");
+        assert_eq!(fails(r"\begin{matrix}"), r"`\begin`");
+        assert_eq!(fails(r"\mathbb{1}"), r"`\mathbb{1}`");
+        assert_eq!(fails("{x"), "unbalanced braces");
+        assert_eq!(fails("x^2^3"), "double script");
+    }
+}
@@ -0,0 +1,309 @@
+//! Blog posts and their front matter.
+//!
+//! Every `blog/*.md` file starts with a metadata block, either YAML between `---`
+//! fences or TOML between `+++` fences:
+//!
+//! ```text
+//! ---
+//! title: Type guidance on APIs using PhantomData
+//! date: 2022-08-06
+//! description: In this writeup I learn about PhantomData.
+//! tags: [Rust]
+//! ---
+//! ```
+
+use crate::error::RenderError;
+use crate::reading::Reading;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    pub title: String,
+    pub date: NaiveDate,
+    /// When the post was last revised, if ever.
+    #[serde(default)]
+    pub updated: Option<NaiveDate>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Drafts are rendered when requested directly but left out of the index.
+    #[serde(default)]
+    pub draft: bool,
+    /// Whether long posts get a table of contents.
+    #[serde(default = "yes")]
+    pub toc: bool,
+}
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub struct Post {
+    pub slug: String,
+    pub path: PathBuf,
+    pub meta: FrontMatter,
+    /// The markdown source with the front matter stripped.
+    pub body: String,
+    /// Hash of the whole source file, to tell when an earlier rendering is stale.
+    pub hash: u64,
+    pub reading: Reading,
+}
+
+impl Post {
+    pub fn last_modified(&self) -> NaiveDate {
+        self.meta
+            .updated
+            .unwrap_or(self.meta.date)
+            .max(self.meta.date)
+    }
+
+    /// When the source last changed, for `Last-Modified`: the file's modification time,
+    /// unless the front matter claims a later date.
+    pub fn modified(&self) -> DateTime<Utc> {
+        let date = self
+            .last_modified()
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc();
+        std::fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .map_or(date, |mtime| date.max(mtime.into()))
+    }
+}
+
+/// A tag together with the number of posts carrying it.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub slug: String,
+    pub count: usize,
+}
+
+/// The URL form of a tag, e.g. `Rust` becomes `rust` and `Data Science` `data-science`.
+/// A `+` or `#` ending a word is spelled out, so `C++` is `cpp` and `C#` is `csharp`
+/// instead of both becoming `c`.
+pub fn tag_slug(tag: &str) -> String {
+    let mut spelled = String::with_capacity(tag.len());
+    let mut previous = ' ';
+    for c in tag.chars() {
+        match c {
+            '+' if previous.is_alphanumeric() || previous == '+' => spelled.push('p'),
+            '#' if previous.is_alphanumeric() => spelled.push_str("sharp"),
+            c => spelled.push(c),
+        }
+        previous = c;
+    }
+
+    spelled
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Every tag used by `posts`, sorted by slug. Tags differing only in case or punctuation
+/// are merged under the spelling of their first use.
+pub fn tags(posts: &[Post]) -> Vec<Tag> {
+    let mut tags = BTreeMap::<String, Tag>::new();
+    for name in posts.iter().flat_map(|post| &post.meta.tags) {
+        let slug = tag_slug(name);
+        tags.entry(slug.clone())
+            .or_insert_with(|| Tag {
+                name: name.clone(),
+                slug,
+                count: 0,
+            })
+            .count += 1;
+    }
+    tags.into_values().collect()
+}
+
+/// Post counts per month, grouped by year, both in ascending order.
+pub fn archive(posts: &[Post]) -> BTreeMap<i32, BTreeMap<u32, usize>> {
+    let mut archive = BTreeMap::<i32, BTreeMap<u32, usize>>::new();
+    for post in posts {
+        *archive
+            .entry(post.meta.date.year())
+            .or_default()
+            .entry(post.meta.date.month())
+            .or_default() += 1;
+    }
+    archive
+}
+
+impl Post {
+    pub fn has_tag(&self, slug: &str) -> bool {
+        self.meta.tags.iter().any(|tag| tag_slug(tag) == slug)
+    }
+}
+
+pub fn blog_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/blog"))
+}
+
+/// Splits `input` into its parsed front matter and the markdown that follows it.
+pub fn split_front_matter(input: &str) -> Result<(FrontMatter, &str)> {
+    let input = input.trim_start_matches('\u{feff}');
+    let (fence, yaml) = if input.starts_with("---") {
+        ("---", true)
+    } else if input.starts_with("+++") {
+        ("+++", false)
+    } else {
+        bail!("missing front matter, expected a `---` or `+++` block at the start of the file");
+    };
+
+    let rest = input[fence.len()..]
+        .strip_prefix('\n')
+        .or_else(|| input[fence.len()..].strip_prefix("\r\n"))
+        .ok_or_else(|| anyhow!("the opening `{fence}` must be on a line of its own"))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            let meta = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let meta = if yaml {
+                serde_yaml_ng::from_str(meta).context("invalid YAML front matter")?
+            } else {
+                toml::from_str(meta).context("invalid TOML front matter")?
+            };
+            return Ok((meta, body));
+        }
+        offset += line.len();
+    }
+
+    bail!("unterminated front matter, missing the closing `{fence}`")
+}
+
+/// Whether `slug` has the shape of a post slug: lowercase ASCII words joined by single
+/// hyphens. Anything else, separators and dots included, is never looked up on disk.
+/// Four digits alone are a year, and `/blog/<year>/` is that year's archive.
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= 128
+        && !(slug.len() == 4 && slug.bytes().all(|b| b.is_ascii_digit()))
+        && slug.split('-').all(|word| {
+            !word.is_empty()
+                && word
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        })
+}
+
+/// Reads and parses a single post.
+pub fn load(path: &Path) -> Result<Post, RenderError> {
+    let slug = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let bytes = std::fs::read(path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::NotFound => RenderError::MissingPost(slug.clone()),
+        _ => RenderError::Io {
+            path: path.to_path_buf(),
+            source,
+        },
+    })?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let hash = hasher.finish();
+    let input = String::from_utf8(bytes).map_err(|source| RenderError::InvalidUtf8 {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let (meta, body) = split_front_matter(&input).map_err(|source| RenderError::FrontMatter {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    Ok(Post {
+        slug,
+        path: path.to_path_buf(),
+        meta,
+        reading: Reading::of(body),
+        body: body.to_string(),
+        hash,
+    })
+}
+
+/// The markdown files under `blog/`, sorted by name.
+pub fn paths() -> Result<Vec<PathBuf>, RenderError> {
+    let dir = blog_dir();
+    let io_error = |source| RenderError::Io {
+        path: dir.clone(),
+        source,
+    };
+    let mut paths = Vec::new();
+
+    for entry in std::fs::read_dir(&dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.extension().is_some_and(|ext| ext == "md") {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Every post under `blog/`, drafts included, newest first.
+pub fn all() -> Result<Vec<Post>, RenderError> {
+    let mut posts = paths()?
+        .iter()
+        .map(|path| load(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Ties are broken by slug so the order never depends on the file system
+    posts.sort_by(|a, b| {
+        b.meta
+            .date
+            .cmp(&a.meta.date)
+            .then_with(|| a.slug.cmp(&b.slug))
+    });
+    Ok(posts)
+}
+
+/// Resolves a slug taken from a URL against the posts that exist under `blog/`.
+///
+/// The slug is only compared against the file names, never joined onto a path, so a
+/// hostile slug can at worst miss. Only the matching post is read and parsed.
+pub fn find(slug: &str) -> Result<Post, RenderError> {
+    if !is_valid_slug(slug) {
+        return Err(RenderError::MissingPost(slug.to_string()));
+    }
+
+    let path = paths()?
+        .into_iter()
+        .find(|path| path.file_stem().is_some_and(|stem| stem == slug))
+        .ok_or_else(|| RenderError::MissingPost(slug.to_string()))?;
+    load(&path)
+}
+
+/// The posts listed on the blog index, newest first.
+pub fn published() -> Result<Vec<Post>, RenderError> {
+    Ok(all()?.into_iter().filter(|post| !post.meta.draft).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_slugs_keep_languages_apart() {
+        assert_eq!(tag_slug("C"), "c");
+        assert_eq!(tag_slug("C++"), "cpp");
+        assert_eq!(tag_slug("C#"), "csharp");
+        assert_eq!(tag_slug("Data Science"), "data-science");
+        assert_eq!(tag_slug("#rust"), "rust");
+        assert_eq!(tag_slug("a + b"), "a-b");
+    }
+}
@@ -0,0 +1,80 @@
+//! Word counts and reading time estimates.
+//!
+//! Words are counted in the prose of a post, captions and footnotes included. Code
+//! blocks are left out of the count and add time per line instead, since code is read
+//! at a very different pace than prose and a long listing would otherwise make a short
+//! post look long, or a few lines of dense code look free.
+
+use maud::{html, Markup};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+const WORDS_PER_MINUTE: usize = 230;
+const CODE_LINES_PER_MINUTE: usize = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reading {
+    pub words: usize,
+    pub code_lines: usize,
+}
+
+fn words(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Every extension a post may use, so their syntax is never mistaken for words.
+pub fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_MATH
+}
+
+impl Reading {
+    pub fn of(markdown: &str) -> Self {
+        let mut reading = Reading::default();
+        let mut in_code_block = false;
+        for event in Parser::new_ext(markdown, options()) {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                Event::Text(text) if in_code_block => {
+                    reading.code_lines += text.lines().filter(|l| !l.trim().is_empty()).count();
+                }
+                Event::Text(text) | Event::Code(text) => reading.words += words(&text),
+                _ => {}
+            }
+        }
+        reading
+    }
+
+    /// The estimated reading time, rounded up to a whole minute.
+    pub fn minutes(&self) -> usize {
+        let seconds =
+            self.words * 60 / WORDS_PER_MINUTE + self.code_lines * 60 / CODE_LINES_PER_MINUTE;
+        seconds.div_ceil(60).max(1)
+    }
+
+    pub fn render(&self) -> Markup {
+        html! {
+            (self.minutes()) " min read · " (self.words) " words"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_is_left_out_of_the_word_count() {
+        let prose = Reading::of("# Title\n\nOne two three.\n");
+        let code = Reading::of("# Title\n\nOne two three.\n\n```rust\nlet a = 1;\n```\n");
+        assert_eq!(prose.words, 4);
+        assert_eq!(code.words, 4);
+        assert_eq!(code.code_lines, 1);
+    }
+}
@@ -0,0 +1,374 @@
+//! Full-text search over the published posts.
+//!
+//! The index is built from the post sources when the server starts, or on first use,
+//! and again after a change under `serve --watch`. Posts are written in English or
+//! Spanish without saying which, so every word is indexed under both its English and
+//! its Spanish stem and queries are stemmed the same way. Words are lowercased and stripped of accents
+//! first, so `mexico` finds "México".
+//!
+//! Results are ranked with BM25, counting matches in the title and tags more than in
+//! the body, and come with a snippet of the body around the first match. The last word
+//! of a query also matches as a prefix, so results follow along while typing.
+//!
+//! Static exports have no server to answer queries, so the search bar falls back to
+//! filtering the cards by their text there.
+
+use crate::error::RenderError;
+use crate::post::{self, Post};
+use maud::{html, Markup};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::{Arc, LazyLock, Mutex};
+
+pub const PATH: &str = "/api/search";
+
+/// Results returned for a query at most.
+const LIMIT: usize = 20;
+
+/// How much more a word counts in the title and the tags than in the body.
+const TITLE_WEIGHT: f32 = 3.0;
+const TAG_WEIGHT: f32 = 2.0;
+
+/// The usual BM25 parameters: how quickly repeating a word stops adding to the score,
+/// and how much long posts are penalized.
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Roughly how many characters of context a snippet shows around its first match.
+const SNIPPET_BEFORE: usize = 60;
+const SNIPPET_LENGTH: usize = 200;
+
+static ENGLISH: LazyLock<Stemmer> = LazyLock::new(|| Stemmer::create(Algorithm::English));
+static SPANISH: LazyLock<Stemmer> = LazyLock::new(|| Stemmer::create(Algorithm::Spanish));
+
+/// The index, until a change to the posts clears it.
+static INDEX: Mutex<Option<Arc<Index>>> = Mutex::new(None);
+
+#[derive(Debug, Deserialize)]
+pub struct Params {
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub query: String,
+    pub results: Vec<Hit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hit {
+    pub slug: String,
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    pub date: String,
+    pub score: f32,
+    /// A piece of the post with the matching words in `<mark>`, as HTML.
+    pub snippet: String,
+}
+
+#[derive(Debug)]
+struct Document {
+    slug: String,
+    title: String,
+    description: String,
+    date: String,
+    /// The body as plain text, for snippets.
+    text: String,
+    /// Number of words, weighted like the postings.
+    length: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    documents: Vec<Document>,
+    /// Weighted occurrences of every stem, by document.
+    postings: BTreeMap<String, Vec<(usize, f32)>>,
+    average_length: f32,
+}
+
+/// Lowercases `word` and strips the accents Spanish and English text uses.
+fn fold(word: &str) -> String {
+    word.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ç' => 'c',
+            c => c,
+        })
+        .collect()
+}
+
+/// The words of `text` with their byte ranges, folded.
+fn words(text: &str) -> impl Iterator<Item = (usize, usize, String)> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(move |word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (start, start + word.len(), fold(word))
+        })
+}
+
+/// The index keys of a folded word: its English and Spanish stems.
+fn stems(word: &str) -> Vec<String> {
+    let english = ENGLISH.stem(word).into_owned();
+    let spanish = SPANISH.stem(word).into_owned();
+    match english == spanish {
+        true => vec![english],
+        false => vec![english, spanish],
+    }
+}
+
+/// The post body as plain text, code included, without markdown syntax.
+fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, crate::reading::options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => {
+                text.push_str(&t)
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableCell,
+            )
+            | Event::Start(Tag::CodeBlock(_)) => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+impl Index {
+    pub fn build(posts: &[Post]) -> Self {
+        let mut index = Index::default();
+
+        for (id, post) in posts.iter().enumerate() {
+            let text = plain_text(&post.body);
+            let mut counts = HashMap::<String, f32>::new();
+            let mut length = 0.0;
+            let tags = post.meta.tags.join(" ");
+            let fields = [
+                (post.meta.title.as_str(), TITLE_WEIGHT),
+                (&tags, TAG_WEIGHT),
+                (&post.meta.description, 1.0),
+                (&text, 1.0),
+            ];
+            for (field, weight) in fields {
+                for (_, _, word) in words(field) {
+                    for stem in stems(&word) {
+                        *counts.entry(stem).or_default() += weight;
+                    }
+                    length += weight;
+                }
+            }
+
+            for (stem, count) in counts {
+                index.postings.entry(stem).or_default().push((id, count));
+            }
+            index.documents.push(Document {
+                slug: post.slug.clone(),
+                title: post.meta.title.clone(),
+                description: post.meta.description.clone(),
+                date: post.meta.date.format("%Y-%m-%d").to_string(),
+                text,
+                length,
+            });
+        }
+
+        let total: f32 = index.documents.iter().map(|doc| doc.length).sum();
+        index.average_length = total / index.documents.len().max(1) as f32;
+        index
+    }
+
+    /// The stems a query word matches, with the prefix matches for the word being typed.
+    fn keys(&self, word: &str, prefix: bool) -> HashSet<&str> {
+        let mut keys: HashSet<&str> = stems(word)
+            .iter()
+            .filter_map(|stem| self.postings.get_key_value(stem))
+            .map(|(key, _)| key.as_str())
+            .collect();
+        if prefix {
+            keys.extend(
+                self.postings
+                    .range::<str, _>((Bound::Included(word), Bound::Unbounded))
+                    .map(|(key, _)| key.as_str())
+                    .take_while(|key| key.starts_with(word)),
+            );
+        }
+        keys
+    }
+
+    fn bm25(&self, key: &str, doc: usize, count: f32) -> f32 {
+        let n = self.documents.len() as f32;
+        let df = self.postings[key].len() as f32;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+        let length = self.documents[doc].length / self.average_length.max(1.0);
+        idf * count * (K1 + 1.0) / (count + K1 * (1.0 - B + B * length))
+    }
+
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let terms: Vec<_> = words(query).map(|(_, _, word)| word).collect();
+        // A query ending in a space is done typing its last word
+        let typing = !query.ends_with(char::is_whitespace);
+
+        // Per document, how many query words it matches and its score
+        let mut scores = HashMap::<usize, (usize, f32)>::new();
+        let mut matched = Vec::new();
+        for (i, term) in terms.iter().enumerate() {
+            let keys = self.keys(term, typing && i + 1 == terms.len());
+            let mut best = HashMap::<usize, f32>::new();
+            for key in &keys {
+                for &(doc, count) in &self.postings[*key] {
+                    let score = self.bm25(key, doc, count);
+                    let best = best.entry(doc).or_default();
+                    *best = best.max(score);
+                }
+            }
+            for (doc, score) in best {
+                let entry = scores.entry(doc).or_default();
+                entry.0 += 1;
+                entry.1 += score;
+            }
+            matched.extend(keys);
+        }
+
+        let mut ranked: Vec<_> = scores.into_iter().collect();
+        // Posts matching more of the query first, then by score, then newest first
+        ranked.sort_by(|(a, (a_terms, a_score)), (b, (b_terms, b_score))| {
+            b_terms
+                .cmp(a_terms)
+                .then(b_score.total_cmp(a_score))
+                .then_with(|| self.documents[*b].date.cmp(&self.documents[*a].date))
+        });
+
+        let matched: HashSet<&str> = matched.into_iter().collect();
+        ranked
+            .into_iter()
+            .take(LIMIT)
+            .map(|(doc, (_, score))| {
+                let doc = &self.documents[doc];
+                Hit {
+                    slug: doc.slug.clone(),
+                    url: format!("/blog/{}/", doc.slug),
+                    title: doc.title.clone(),
+                    description: doc.description.clone(),
+                    date: doc.date.clone(),
+                    score,
+                    snippet: snippet(&doc.text, &matched).into_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The nearest char boundary at or before `at`.
+fn floor(text: &str, mut at: usize) -> usize {
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+/// A window of `text` around the first word matching one of `keys`, with the matches
+/// marked. Posts matching only in the title or tags get their opening instead.
+fn snippet(text: &str, keys: &HashSet<&str>) -> Markup {
+    let hits: Vec<_> = words(text)
+        .filter(|(_, _, word)| {
+            keys.contains(word.as_str())
+                || stems(word).iter().any(|stem| keys.contains(stem.as_str()))
+        })
+        .map(|(start, end, _)| (start, end))
+        .collect();
+
+    let first = hits.first().map_or(0, |(start, _)| *start);
+    let mut start = floor(text, first.saturating_sub(SNIPPET_BEFORE));
+    if start > 0 {
+        // Start on a whole word
+        start = text[start..first]
+            .find(char::is_whitespace)
+            .map_or(first, |space| start + space + 1);
+    }
+    let mut end = floor(text, (start + SNIPPET_LENGTH).min(text.len()));
+    if end < text.len() {
+        // End on a whole word too, unless the window is one long word
+        end = text[start..end]
+            .rfind(char::is_whitespace)
+            .map_or(end, |space| start + space);
+    }
+
+    let mut pieces = Vec::new();
+    let mut at = start;
+    for &(hit_start, hit_end) in &hits {
+        if hit_start < start || hit_end > end {
+            continue;
+        }
+        pieces.push((&text[at..hit_start], false));
+        pieces.push((&text[hit_start..hit_end], true));
+        at = hit_end;
+    }
+    pieces.push((&text[at..end], false));
+
+    html! {
+        @if start > 0 { "…" }
+        @for (piece, marked) in pieces {
+            @if marked { mark { (piece) } } @else { (piece) }
+        }
+        @if end < text.len() { "…" }
+    }
+}
+
+/// The index for the published posts, built on the first call after a `clear`.
+pub fn index() -> Result<Arc<Index>, RenderError> {
+    let mut current = INDEX.lock().unwrap_or_else(|err| err.into_inner());
+    match &*current {
+        Some(index) => Ok(index.clone()),
+        None => Ok(current
+            .insert(Arc::new(Index::build(&post::published()?)))
+            .clone()),
+    }
+}
+
+/// Drops the index, so the next search rebuilds it from the posts as they are now.
+pub fn clear() {
+    *INDEX.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+pub fn search(query: &str) -> Result<Response, RenderError> {
+    Ok(Response {
+        query: query.to_string(),
+        results: index()?.search(query),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_inside_a_long_word() {
+        // A match deep inside a path or URL has no whitespace to end the window on
+        // after where it starts
+        let text = format!("intro {}needle{} outro", "x/".repeat(100), "/y".repeat(100));
+        let snippet = snippet(&text, &HashSet::from(["needl"])).into_string();
+        assert!(snippet.starts_with("…<mark>needle</mark>/y/y"));
+        assert!(snippet.ends_with("/y…"));
+    }
+
+    #[test]
+    fn snippet_marks_matches() {
+        let text = "Routers forward packets between networks.";
+        let snippet = snippet(text, &HashSet::from(["packet"])).into_string();
+        assert_eq!(
+            snippet,
+            "Routers forward <mark>packets</mark> between networks."
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Site configuration, loaded from `site.toml`.
+//!
+//! Everything that identifies the owner of the site lives in there, so the same binary
+//! can render somebody else's site by swapping the file.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Site {
+    /// Shown in the nav bar, the footer and every page title.
+    pub author: String,
+    /// Absolute URL the site is published at, without a trailing slash.
+    pub url: String,
+    pub description: String,
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub footer: String,
+    /// HTML shown on the home page.
+    pub about: String,
+    #[serde(default)]
+    pub nav: Vec<NavItem>,
+    #[serde(default)]
+    pub social: Vec<Social>,
+    #[serde(default)]
+    pub contact: Contact,
+    #[serde(default)]
+    pub robots: Robots,
+    #[serde(default)]
+    pub markdown: Markdown,
+    #[serde(default)]
+    pub assets: Assets,
+    #[serde(default)]
+    pub cache: Cache,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NavItem {
+    pub label: String,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Icon {
+    Github,
+    Linkedin,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Social {
+    pub icon: Icon,
+    pub href: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Contact {
+    #[serde(default)]
+    pub items: Vec<ContactItem>,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContactItem {
+    pub label: String,
+    pub href: String,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Robots {
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+/// GitHub-flavoured extensions to markdown, all enabled unless turned off.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Markdown {
+    pub tables: bool,
+    pub footnotes: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Markdown {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Assets {
+    /// Load third-party scripts and styles from their CDN instead of `static/vendor/`.
+    #[serde(default)]
+    pub cdn: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cache {
+    /// How many rendered posts to keep in memory, `0` to render every request.
+    #[serde(default = "default_cache_size")]
+    pub size: usize,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            size: default_cache_size(),
+        }
+    }
+}
+
+fn default_cache_size() -> usize {
+    64
+}
+
+fn default_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_port() -> u16 {
+    8000
+}
+
+static SITE: OnceLock<Site> = OnceLock::new();
+
+pub fn default_path() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/site.toml"))
+}
+
+/// Reads and validates a configuration file.
+pub fn load(path: &Path) -> Result<Site> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let site: Site =
+        toml::from_str(&input).with_context(|| format!("invalid {}", path.display()))?;
+
+    let invalid = |key: &str, why: &str| format!("invalid {}: `{key}` {why}", path.display());
+    if !(site.url.starts_with("https://") || site.url.starts_with("http://")) {
+        bail!(invalid("url", "must start with http:// or https://"));
+    }
+    if site.url.ends_with('/') {
+        bail!(invalid("url", "must not end with a slash"));
+    }
+    if site.author.trim().is_empty() {
+        bail!(invalid("author", "must not be empty"));
+    }
+    if let Some(path) = site.robots.disallow.iter().find(|p| !p.starts_with('/')) {
+        bail!(invalid(
+            "robots.disallow",
+            &format!("entry {path:?} must start with a slash")
+        ));
+    }
+
+    Ok(site)
+}
+
+/// Loads the configuration every other module reads. Must run before the first `get`
+/// to have errors reported instead of panicking.
+pub fn init(path: &Path) -> Result<()> {
+    let site = load(path)?;
+    // A second init keeps the first configuration
+    let _ = SITE.set(site);
+    Ok(())
+}
+
+pub fn get() -> &'static Site {
+    SITE.get_or_init(|| load(&default_path()).unwrap_or_else(|err| panic!("{err:#}")))
+}
@@ -0,0 +1,73 @@
+//! `sitemap.xml` and `robots.txt`.
+
+use crate::feed::escape_xml;
+use crate::post::{self, Post};
+use crate::site;
+
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+pub const ROBOTS_PATH: &str = "/robots.txt";
+
+fn url(loc: &str, lastmod: Option<chrono::NaiveDate>) -> String {
+    let base = &site::get().url;
+    match lastmod {
+        Some(lastmod) => format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            escape_xml(&format!("{base}{loc}")),
+            lastmod.format("%Y-%m-%d"),
+        ),
+        None => format!(
+            "<url><loc>{}</loc></url>",
+            escape_xml(&format!("{base}{loc}"))
+        ),
+    }
+}
+
+/// Lists every page of the site; posts carry their last modification date and the blog
+/// index that of its most recently changed post.
+pub fn sitemap(posts: &[Post]) -> String {
+    let mut urls = vec![
+        url("/", None),
+        url("/contact/", None),
+        url("/blog/", posts.iter().map(Post::last_modified).max()),
+    ];
+    urls.push(url("/blog/tags/", None));
+    urls.extend(
+        post::tags(posts)
+            .iter()
+            .map(|tag| url(&format!("/blog/tags/{}/", tag.slug), None)),
+    );
+    urls.push(url("/blog/archive/", None));
+    for (year, months) in post::archive(posts) {
+        urls.push(url(&format!("/blog/{year}/"), None));
+        urls.extend(
+            months
+                .into_keys()
+                .map(|month| url(&format!("/blog/{year}/{month:02}/"), None)),
+        );
+    }
+    urls.extend(
+        posts
+            .iter()
+            .map(|post| url(&format!("/blog/{}/", post.slug), Some(post.last_modified()))),
+    );
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#,
+        urls.concat()
+    )
+}
+
+/// Allows everything but the `robots.disallow` paths from `site.toml`.
+pub fn robots() -> String {
+    let site = site::get();
+    let base = &site.url;
+    let mut out = String::from("User-agent: *\n");
+    if site.robots.disallow.is_empty() {
+        out.push_str("Allow: /\n");
+    }
+    for path in &site.robots.disallow {
+        out.push_str(&format!("Disallow: {path}\n"));
+    }
+    out.push_str(&format!("\nSitemap: {base}{SITEMAP_PATH}\n"));
+    out
+}
@@ -0,0 +1,86 @@
+//! Heading IDs, permalinks and the table of contents for long posts.
+//!
+//! `render_post` gives every heading an ID while rendering and collects it here. IDs
+//! come from an explicit `{#custom-id}` after the heading text when there is one, and
+//! from the slugified text otherwise, and never repeat one used earlier in the page,
+//! footnotes included. The outline lists the `##` and `###` sections, and is only shown
+//! once a post has enough of them to be worth navigating.
+
+use maud::{html, Markup};
+use pulldown_cmark::HeadingLevel;
+use std::collections::HashSet;
+
+/// Fewer sections than this read fine without an outline.
+pub const MIN_SECTIONS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: HeadingLevel,
+    pub id: String,
+    /// The heading as plain text, without markup.
+    pub text: String,
+}
+
+/// Hands out heading IDs, suffixing repeats with `-1`, `-2`, … so every one stays unique
+/// within the page.
+#[derive(Debug, Default)]
+pub struct Ids(HashSet<String>);
+
+impl Ids {
+    /// Marks an ID as taken without generating it, for IDs the author chose and IDs other
+    /// parts of the page use. Returns whether it was still free.
+    pub fn reserve(&mut self, id: &str) -> bool {
+        self.0.insert(id.to_string())
+    }
+
+    pub fn unique(&mut self, text: &str) -> String {
+        let base = match crate::post::tag_slug(text) {
+            slug if slug.is_empty() => "section".to_string(),
+            slug => slug,
+        };
+        self.suffixed(base)
+    }
+
+    /// `base`, or the first of its suffixed versions that is still free.
+    pub fn suffixed(&mut self, base: String) -> String {
+        let mut id = base.clone();
+        let mut n = 0;
+        while self.0.contains(&id) {
+            n += 1;
+            id = format!("{base}-{n}");
+        }
+        self.0.insert(id.clone());
+        id
+    }
+}
+
+/// The "#" link shown next to a heading on hover.
+pub fn permalink(id: &str) -> Markup {
+    html! {
+        a class="anchor" href=(format!("#{id}")) aria-label="Link to this section" { "#" }
+    }
+}
+
+fn is_section(heading: &&Heading) -> bool {
+    matches!(heading.level, HeadingLevel::H2 | HeadingLevel::H3)
+}
+
+/// The outline of a post, or `None` when it is too short to need one.
+pub fn render(headings: &[Heading]) -> Option<Markup> {
+    let sections: Vec<_> = headings.iter().filter(is_section).collect();
+    if sections.len() < MIN_SECTIONS {
+        return None;
+    }
+
+    Some(html! {
+        ul class="text-sm space-y-1" {
+            @for heading in sections {
+                li class=[(heading.level == HeadingLevel::H3).then_some("pl-4")] {
+                    a class="hover:text-orange-500" href=(format!("#{}", heading.id)) {
+                        (heading.text)
+                    }
+                }
+            }
+        }
+    })
+}